@@ -0,0 +1,422 @@
+//! Dictionary matching engine.
+//!
+//! An [`Analyzer`] owns the loaded dictionary and the [`Config`] selected on
+//! the command line and works out on which guess a cracker walking the
+//! dictionary would hit a candidate password. Nothing here prints; callers get
+//! a [`MatchReport`] back and decide how to present it.
+use crate::charset::normalize;
+use crate::decompose::{Decomposer, Decomposition};
+use crate::dictionary::Dictionary;
+use crate::keyboard::{Keyboard, KeyboardWalk, Layout};
+use crate::markov::MarkovEstimate;
+use crate::policy::{Policy, Rating, Violation};
+use crate::strategy::{GuessStrategy, Hit, StrategyRegistry};
+use crate::{calculate_entropy, log2, Config};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Outcome of matching one candidate against the dictionary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchReport {
+    /// Name of the strategy that found the candidate, `None` if nothing
+    /// matched.
+    pub strategy: Option<String>,
+    /// Guess number of the match, or the number of guesses checked when
+    /// nothing matched.
+    pub guess_number: u64,
+    /// Dictionary word the matching guess was built from.
+    pub word: Option<String>,
+    /// File that word was loaded from.
+    pub source: Option<String>,
+    /// Entropy implied by the guess number, `f64::MAX` when nothing matched.
+    pub entropy: f64,
+}
+
+impl MatchReport {
+    fn found(strategy: &dyn GuessStrategy, guess_number: u64, hit: Hit, dict: &Dictionary) -> Self {
+        MatchReport {
+            strategy: Some(strategy.name().to_string()),
+            guess_number,
+            word: dict.words().get(hit.word).cloned(),
+            source: dict.source_of(hit.word).map(str::to_string),
+            entropy: log2(2.0 * guess_number as f64),
+        }
+    }
+
+    fn not_found(guess_number: u64) -> Self {
+        MatchReport {
+            strategy: None,
+            guess_number,
+            word: None,
+            source: None,
+            entropy: f64::MAX,
+        }
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.strategy.is_some()
+    }
+}
+
+/// One strategy's part in [`Analyzer::trace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub strategy: String,
+    /// Guesses the strategy tries over the whole dictionary.
+    pub guesses: u64,
+    /// The match this strategy finds, numbered from the start of the
+    /// pipeline as if every earlier strategy had missed.
+    pub report: Option<MatchReport>,
+}
+
+/// Everything worked out about one candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    /// The lowest of the charset estimate and every estimate below.
+    pub entropy: f64,
+    /// Dictionary match, `None` when the dictionary is empty.
+    pub report: Option<MatchReport>,
+    pub walk: Option<KeyboardWalk>,
+    pub decomposition: Option<Decomposition>,
+    /// Guesses estimated by the `--markov` model.
+    pub markov: Option<MarkovEstimate>,
+    /// Times the candidate appears in the `--breach-db` corpus.
+    pub breach_count: Option<u64>,
+    /// Rating under the policy, "very weak" for any breached password.
+    pub rating: Rating,
+    /// Rules of the policy the candidate breaks; empty when it is accepted.
+    pub violations: Vec<Violation>,
+}
+
+static DEFAULT_POLICY: Policy = Policy::DEFAULT;
+
+/// Matches candidate passwords against a dictionary using a
+/// [`StrategyRegistry`], by default the one described by its [`Config`].
+pub struct Analyzer {
+    dictionary: Dictionary,
+    config: Config,
+    registry: StrategyRegistry,
+    /// Guess count of each registered strategy over the whole dictionary.
+    scales: Vec<u64>,
+    keyboard: Option<Keyboard>,
+    decomposer: Option<Decomposer>,
+}
+
+impl Analyzer {
+    pub fn new(dictionary: impl Into<Dictionary>, config: Config) -> Self {
+        let registry = StrategyRegistry::from_config(&config);
+        Self::with_registry(dictionary, config, registry)
+    }
+
+    /// Uses `registry` in place of the pipeline derived from `config`.
+    pub fn with_registry(
+        dictionary: impl Into<Dictionary>,
+        config: Config,
+        mut registry: StrategyRegistry,
+    ) -> Self {
+        let dictionary = dictionary.into();
+        registry.prepare(dictionary.words());
+        let scales = registry
+            .iter()
+            .map(|s| s.guess_count(dictionary.words()))
+            .collect();
+        let keyboard = config.keyboard.map(Keyboard::new);
+        let decomposer = config.decompose.then(|| {
+            Decomposer::new(
+                dictionary.words(),
+                config.keyboard.unwrap_or(Layout::Qwerty),
+            )
+        });
+        Analyzer {
+            dictionary,
+            config,
+            registry,
+            scales,
+            keyboard,
+            decomposer,
+        }
+    }
+
+    /// Gives back the dictionary and configuration, e.g. to rebuild the
+    /// analyzer with different options.
+    pub fn into_parts(self) -> (Dictionary, Config) {
+        (self.dictionary, self.config)
+    }
+
+    pub fn passwords(&self) -> &[String] {
+        self.dictionary.words()
+    }
+
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The `--policy` policy, or the built-in one.
+    pub fn policy(&self) -> &Policy {
+        self.config.policy.as_ref().unwrap_or(&DEFAULT_POLICY)
+    }
+
+    pub fn registry(&self) -> &StrategyRegistry {
+        &self.registry
+    }
+
+    /// Adds a strategy after the ones already registered.
+    pub fn register(&mut self, mut strategy: Box<dyn GuessStrategy>) -> &mut Self {
+        strategy.prepare(self.dictionary.words());
+        self.scales
+            .push(strategy.guess_count(self.dictionary.words()));
+        self.registry.register(strategy);
+        self
+    }
+
+    /// Tries each registered strategy in order. Guesses spent by strategies
+    /// that did not find the candidate count towards the guess number of the
+    /// one that does. The candidate is compared in NFKC form, like the
+    /// dictionary words.
+    pub fn analyze(&self, password: &str) -> MatchReport {
+        let password = &*normalize(password);
+        let passwords = self.dictionary.words();
+        log::info!("passwords size = {}", passwords.len());
+        let mut password_scale: u64 = 0;
+        for (strategy, scale) in self.registry.iter().zip(&self.scales) {
+            if let Some(hit) = strategy.find(password, passwords) {
+                let guess_number = password_scale.saturating_add(hit.guess);
+                return MatchReport::found(strategy, guess_number, hit, &self.dictionary);
+            }
+            password_scale = password_scale.saturating_add(*scale);
+        }
+        MatchReport::not_found(password_scale)
+    }
+
+    /// Like [`Self::analyze`], but asks every strategy rather than stopping
+    /// at the first match.
+    pub fn trace(&self, password: &str) -> Vec<TraceStep> {
+        let password = &*normalize(password);
+        let passwords = self.dictionary.words();
+        let mut password_scale: u64 = 0;
+        let mut steps = Vec::new();
+        for (strategy, &scale) in self.registry.iter().zip(&self.scales) {
+            let report = strategy.find(password, passwords).map(|hit| {
+                let guess_number = password_scale.saturating_add(hit.guess);
+                MatchReport::found(strategy, guess_number, hit, &self.dictionary)
+            });
+            steps.push(TraceStep {
+                strategy: strategy.name().to_string(),
+                guesses: scale,
+                report,
+            });
+            password_scale = password_scale.saturating_add(scale);
+        }
+        steps
+    }
+
+    /// Checks whether `password` is a walk on the layout chosen with
+    /// `--keyboard`; always `None` when no layout was chosen.
+    pub fn keyboard_walk(&self, password: &str) -> Option<KeyboardWalk> {
+        self.keyboard.as_ref()?.find_walk(password)
+    }
+
+    /// Splits `password` into pattern segments when `--decompose` is on.
+    /// Keyboard-walk segments use the `--keyboard` layout, qwerty by default.
+    pub fn decompose(&self, password: &str) -> Option<Decomposition> {
+        Some(self.decomposer.as_ref()?.decompose(password))
+    }
+
+    /// Guesses the `--markov` model needs for `password`; `None` without a
+    /// model.
+    pub fn markov(&self, password: &str) -> Option<MarkovEstimate> {
+        let model = self.config.markov.as_ref()?;
+        Some(model.estimate(&normalize(password)))
+    }
+
+    /// Runs every enabled analysis on `password` and keeps the lowest
+    /// entropy estimate. A password found in the breach corpus is rated
+    /// "very weak" whatever its entropy.
+    pub fn evaluate(&self, password: &str) -> Evaluation {
+        let mut entropy = calculate_entropy(password);
+        let report = (!self.dictionary.is_empty()).then(|| self.analyze(password));
+        let walk = self.keyboard_walk(password);
+        let decomposition = self.decompose(password);
+        let markov = self.markov(password);
+        if let Some(report) = &report {
+            entropy = entropy.min(report.entropy);
+        }
+        if let Some(walk) = &walk {
+            entropy = entropy.min(walk.entropy());
+        }
+        if let Some(decomposition) = &decomposition {
+            entropy = entropy.min(decomposition.entropy());
+        }
+        if let Some(markov) = &markov {
+            entropy = entropy.min(markov.entropy());
+        }
+        let breach_count = self.config.breach_db.as_ref().map(|db| db.count(password));
+        let rating = match breach_count {
+            Some(count) if count > 0 => Rating::VeryWeak,
+            _ => self.policy().rate(entropy),
+        };
+        Evaluation {
+            entropy,
+            report,
+            walk,
+            decomposition,
+            markov,
+            breach_count,
+            rating,
+            violations: self.policy().check(password, rating),
+        }
+    }
+
+    /// Evaluates `passwords` on up to `jobs` threads sharing this analyzer.
+    /// Results come back in the same order as `passwords`.
+    pub fn evaluate_all(&self, passwords: &[String], jobs: usize) -> Vec<Evaluation> {
+        if jobs <= 1 || passwords.len() <= 1 {
+            return passwords.iter().map(|p| self.evaluate(p)).collect();
+        }
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; passwords.len()]);
+        thread::scope(|scope| {
+            for _ in 0..jobs.min(passwords.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(password) = passwords.get(i) else {
+                        break;
+                    };
+                    let evaluation = self.evaluate(password);
+                    results.lock().unwrap()[i] = Some(evaluation);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.expect("every candidate is evaluated"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_analyze_no_match() {
+        let analyzer = Analyzer::new(words(&["<PASSWORD>", "123456"]), Config::new());
+        let report = analyzer.analyze("password");
+        assert!(!report.is_match());
+        assert_eq!(report.guess_number, 2);
+        assert_eq!(report.entropy, f64::MAX);
+    }
+
+    #[test]
+    fn test_analyze_strategy_order() {
+        let config = Config {
+            case_sensitive: true,
+            digit_append: true,
+            num_digits: 1,
+            double_check: true,
+            leet: true,
+            ..Config::default()
+        };
+        let analyzer = Analyzer::new(words(&["ab", "cd"]), config);
+
+        // basic: rank in the list
+        assert_eq!(analyzer.analyze("cd").guess_number, 2);
+        // case: 2 + (2^2 - 1)
+        let report = analyzer.analyze("AB");
+        assert_eq!(report.strategy.as_deref(), Some("case"));
+        assert_eq!(report.guess_number, 5);
+        // digit-append: 2 + 6 + 10 + 4
+        let report = analyzer.analyze("cd3");
+        assert_eq!(report.strategy.as_deref(), Some("digit-append"));
+        assert_eq!(report.guess_number, 22);
+        // double: 2 + 6 + 20 + 2 + 1
+        let report = analyzer.analyze("cdab");
+        assert_eq!(report.strategy.as_deref(), Some("double"));
+        assert_eq!(report.guess_number, 31);
+        // leet: 2 + 6 + 20 + 4 + (3 * 3 - 1)
+        let report = analyzer.analyze("@8");
+        assert_eq!(report.strategy.as_deref(), Some("leet"));
+        assert_eq!(report.guess_number, 40);
+        assert_eq!(report.entropy, log2(80.0));
+    }
+
+    #[test]
+    fn test_trace_asks_every_strategy() {
+        let config = Config {
+            case_sensitive: true,
+            leet: true,
+            ..Config::default()
+        };
+        let analyzer = Analyzer::new(words(&["ab", "cd"]), config);
+        let steps = analyzer.trace("ab");
+        let found: Vec<_> = steps
+            .iter()
+            .map(|step| {
+                (
+                    step.strategy.as_str(),
+                    step.guesses,
+                    step.report.as_ref().map(|r| r.guess_number),
+                )
+            })
+            .collect();
+        // case: 2 + (2^2 - 1); leet: 2 + 6 + (3 * 3 - 1)
+        assert_eq!(
+            found,
+            vec![
+                ("basic", 2, Some(1)),
+                ("case", 6, Some(5)),
+                ("leet", 8, Some(16))
+            ]
+        );
+        assert_eq!(analyzer.analyze("ab").guess_number, 1);
+    }
+
+    #[test]
+    fn test_indexed_lookups_keep_first_rank() {
+        let config = Config {
+            case_sensitive: true,
+            double_check: true,
+            ..Config::default()
+        };
+        let analyzer = Analyzer::new(words(&["pass", "ab", "word", "pass", "a", "bword"]), config);
+
+        // repeated words keep the position of their first occurrence
+        assert_eq!(analyzer.analyze("pass").guess_number, 1);
+        // case: 6 + (15 + 3 + 15)
+        assert_eq!(analyzer.analyze("WORD").guess_number, 39);
+        // double: "ab" (1) + "word" (2) is tried before "a" (4) + "bword"
+        let report = analyzer.analyze("abword");
+        assert_eq!(report.strategy.as_deref(), Some("double"));
+        assert_eq!(report.guess_number, 6 + 80 + 6 + 2 + 1);
+        assert_eq!(report.word.as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn test_evaluate_all_keeps_order() {
+        let config = Config {
+            digit_append: true,
+            num_digits: 2,
+            ..Config::default()
+        };
+        let analyzer = Analyzer::new(words(&["ab", "cd", "ef"]), config);
+        let passwords = words(&["cd", "ef7", "zz", "ab42", "Tr0ub4dor&3", "ef"]);
+        let serial = analyzer.evaluate_all(&passwords, 1);
+        assert_eq!(analyzer.evaluate_all(&passwords, 4), serial);
+        let guesses: Vec<_> = serial
+            .iter()
+            .map(|e| e.report.as_ref().unwrap().guess_number)
+            .collect();
+        assert_eq!(guesses, vec![2, 3 + 220 + 8, 3 + 330, 3 + 10 + 43, 333, 3]);
+    }
+}
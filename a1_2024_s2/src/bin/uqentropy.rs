@@ -1,619 +1,494 @@
-use anyhow::Result;
-use std::collections::HashMap;
+use rustyline::DefaultEditor;
 use std::env;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
 use std::process::exit;
-use thiserror::Error;
+use uqentropy::cli::{self, BUILD_USAGE_MSG, TRAIN_USAGE_MSG};
 use uqentropy::error::{UQEntropyError, UQEntropyResult};
-use uqentropy::utils::log::init_logging;
-
-struct Config {
-    leet: bool,
-    case_sensitive: bool,
-    digit_append: bool,
-    double_check: bool,
-    num_digits: usize,
-}
-
-impl Config {
-    fn new() -> Self {
-        Config {
-            leet: false,
-            case_sensitive: false,
-            digit_append: false,
-            double_check: false,
-            num_digits: 0,
-        }
-    }
-}
-impl Default for Config {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-// 定义字符串常量
-const USAGE_MSG: &str =
-    "Usage: ./uqentropy [--leet] [--double] [--digit-append 1..8] [--case] [listfilename ...]";
-
-#[derive(Debug)]
+use uqentropy::generate::{Alphabet, GenerateError, Generator};
+use uqentropy::lint::FileLint;
+use uqentropy::policy::CharClass;
+use uqentropy::repl::{self, Command, Stats};
+use uqentropy::uqd;
+use uqentropy::utils::log::{init_logging, Redacted};
+use uqentropy::{
+    calculate_entropy, check_password_is_valid, floor_to_one_decimal, Analyzer, Config,
+    Decomposition, Dictionary, Evaluation, MarkovModel, MatchReport, OutputFormat, Policy, Record,
+    RecordWriter,
+};
+
+#[derive(Debug, PartialEq)]
 enum ExitCodes {
     Usage = 2,
     InvalidFile = 20,
     NoStrong = 14, // 确保NoStrong对应的值为14，与测试期望一致
 }
 
-fn log2(x: f64) -> f64 {
-    x.log2()
-}
-
-fn calculate_entropy(password: &str) -> f64 {
-    let mut has_lower = false;
-    let mut has_upper = false;
-    let mut has_digit = false;
-    let mut has_symbol = false;
-
-    for c in password.chars() {
-        if c.is_ascii_lowercase() {
-            has_lower = true;
-        } else if c.is_ascii_uppercase() {
-            has_upper = true;
-        } else if c.is_ascii_digit() {
-            has_digit = true;
-        } else {
-            has_symbol = true;
+impl From<&UQEntropyError> for ExitCodes {
+    fn from(err: &UQEntropyError) -> Self {
+        match err {
+            UQEntropyError::Usage(_) => ExitCodes::Usage,
+            UQEntropyError::NoStrong | UQEntropyError::Generate(GenerateError::Unreachable(_)) => {
+                ExitCodes::NoStrong
+            }
+            // 只有一个不同单词的 --words 文件是输入文件的问题
+            _ => ExitCodes::InvalidFile,
         }
     }
+}
 
-    let mut s = 0;
-    if has_digit {
-        s += 10;
-    }
-    if has_lower {
-        s += 26;
-    }
-    if has_upper {
-        s += 26;
-    }
-    if has_symbol {
-        s += 32;
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    // 所有错误都传播到这里，统一打印并映射为退出码
+    if let Err(err) = run(&args) {
+        print_error(&err);
+        exit(ExitCodes::from(&err) as i32);
     }
+}
 
-    let result = password.len() as f64 * log2(s as f64);
-    floor_to_one_decimal(result)
+/// 打印错误：字典文件中每个含无效字符的行各报告一次
+fn print_error(err: &UQEntropyError) {
+    match err {
+        // 没有强密码的提示已经由 finish 打印在标准输出上
+        UQEntropyError::NoStrong => {}
+        // 只报告含无效字符的行，空行没有提示
+        UQEntropyError::InvalidCharacters { lines, .. } => {
+            for _ in 0..*lines {
+                eprintln!("{}", err);
+            }
+        }
+        UQEntropyError::ReadFailed {
+            file,
+            invalid_lines,
+        }
+        | UQEntropyError::EmptyDictionary {
+            file,
+            invalid_lines,
+        } => {
+            print_error(&UQEntropyError::InvalidCharacters {
+                file: file.clone(),
+                lines: *invalid_lines,
+            });
+            eprintln!("{}", err);
+        }
+        _ => eprintln!("{}", err),
+    }
 }
 
-fn map_to_strength(entropy: f64) -> &'static str {
-    if entropy < 35.0 {
-        "very weak"
-    } else if entropy < 60.0 {
-        "weak"
-    } else if entropy < 120.0 {
-        "strong"
+fn run(args: &[String]) -> UQEntropyResult<()> {
+    if args.len() == 2 && args[1] == "--help" {
+        print!("{}", cli::HELP_MSG);
+        return Ok(());
+    }
+    match args.get(1).map(String::as_str) {
+        Some("build-dict") => return build_dict(&args[2..]),
+        Some("train") => return train(&args[2..]),
+        Some("generate") => return generate(&args[2..]),
+        Some("lint") => return lint(&args[2..]),
+        _ => {}
+    }
+    let (config, filenames) = cli::parse_arguments(args)?;
+    // 只有给出 --log-file 或 --log-level 时才记录日志
+    init_logging(config.log_file.as_deref(), config.log_level)
+        .map_err(|_| UQEntropyError::UnwritableFile(config.log_file.clone().unwrap_or_default()))?;
+
+    let dictionary = Dictionary::load(&filenames)?;
+    let analyzer = Analyzer::new(dictionary, config);
+    if is_interactive(analyzer.config()) {
+        run_repl(analyzer)
     } else {
-        "very strong"
+        process_user_input(&analyzer)
     }
 }
 
-fn check_password_is_valid(password: &str) -> bool {
-    if password.is_empty() {
-        return false;
-    }
-    // 只允许可打印的ASCII字符，不包括控制字符
-    password
-        .chars()
-        .all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
-}
-#[warn(clippy::too_many_lines)]
-fn read_file(filenames: &[String], passwords: &mut Vec<String>, config: &Config) {
-    // 打印文件数量
-    log::info!(
-        "Reading {} file{}",
-        filenames.len(),
-        if filenames.len() == 1 { "" } else { "s" }
-    );
-    for fname in filenames {
-        if let Err(_) = read_single_file(fname, passwords, config) {
-            exit(ExitCodes::InvalidFile as i32);
-        }
-    }
+/// build-dict 子命令：把文本字典编译成 .uqd 文件
+fn build_dict(args: &[String]) -> UQEntropyResult<()> {
+    let (filenames, output) = cli::subcommand_args(args, BUILD_USAGE_MSG)?;
+    let dictionary = Dictionary::load(&filenames)?;
+    File::create(&output)
+        .and_then(|file| uqd::write(&dictionary, BufWriter::new(file)))
+        .map_err(|_| UQEntropyError::UnwritableFile(output))
 }
 
-fn read_single_file(
-    fname: &String,
-    passwords: &mut Vec<String>,
-    _config: &Config,
-) -> Result<(), std::io::Error> {
-    let file = File::open(fname);
-    let file = match file {
-        Ok(f) => f,
-        Err(_) => {
-            eprintln!("uqentropy: unable to open file \"{}\" for reading", fname);
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Unable to open file",
-            ));
-        }
-    };
+/// train 子命令：用文本字典训练字符 n-gram 模型
+fn train(args: &[String]) -> UQEntropyResult<()> {
+    let (filenames, output) = cli::subcommand_args(args, TRAIN_USAGE_MSG)?;
+    let model = MarkovModel::train(Dictionary::load(&filenames)?.words());
+    File::create(&output)
+        .and_then(|file| model.write(BufWriter::new(file)))
+        .map_err(|_| UQEntropyError::UnwritableFile(output))
+}
 
-    let reader = BufReader::new(file);
-    let mut has_valid_password = false;
-    let mut invalid_lines = 0;
-    let mut total_lines = 0;
-    for line_result in reader.lines() {
-        total_lines += 1;
-        match line_result {
-            Ok(line) => {
-                if process_line(line.trim(), passwords, fname) {
-                    has_valid_password = true;
-                } else {
-                    invalid_lines += 1;
-                }
+/// lint 子命令：逐个检查文本字典，报告统计信息、问题位置和文件之间的重叠
+fn lint(args: &[String]) -> UQEntropyResult<()> {
+    let args = cli::lint_args(args)?;
+    let mut failed = 0;
+    let mut linted: Vec<FileLint> = Vec::new();
+    for fname in args {
+        let found = match FileLint::read(fname) {
+            Ok(found) => found,
+            Err(err) => {
+                eprintln!("{}", err);
+                failed += 1;
+                continue;
             }
-            Err(_) => {
-                eprintln!("uqentropy: error reading file \"{}\"", fname);
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Error reading file",
-                ));
+        };
+        print_lint(&found);
+        for other in &linted {
+            let shared = found.overlap(other);
+            if shared > 0 {
+                println!("{}: {} token(s) also in {}", found.file, shared, other.file);
             }
         }
+        if found.has_errors() {
+            failed += 1;
+        }
+        linted.push(found);
     }
-    log::info!("total lines: {}", total_lines);
-    if !has_valid_password {
-        eprintln!("uqentropy: \"{}\" does not contain any passwords", fname);
-        std::io::stderr().flush().unwrap();
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "No passwords found",
-        ));
-    }
-    if invalid_lines > 0 {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Invalid lines found",
-        ));
+    if failed > 0 {
+        return Err(UQEntropyError::LintFailed(failed));
     }
     Ok(())
 }
 
-fn process_line(line: &str, passwords: &mut Vec<String>, fname: &str) -> bool {
-    // 检查行中是否包含无效字符（除了ASCII空白字符）
-    for c in line.chars() {
-        if !c.is_ascii() || (c.is_ascii() && !c.is_ascii_graphic() && !c.is_ascii_whitespace()) {
-            log::debug!("Invalid character '{}' found in line: {}", c, line);
-            eprintln!("uqentropy: invalid character found in file \"{}\"", fname);
-            return false;
+fn print_lint(found: &FileLint) {
+    println!(
+        "{}: {} token(s), {} duplicate(s), {} very weak",
+        found.file, found.tokens, found.duplicates, found.very_weak
+    );
+    for problem in &found.problems {
+        println!("{}: {}", found.file, problem);
+    }
+    if found.tokens == 0 {
+        println!("{}: does not contain any passwords", found.file);
+    }
+}
+
+/// generate 子命令：生成达到目标熵的随机密码或口令短语
+fn generate(args: &[String]) -> UQEntropyResult<()> {
+    let options = cli::generate_options(args)?;
+    let mut dictionary = Dictionary::new();
+    let alphabet = match (&options.words, options.classes) {
+        (Some(fname), _) => {
+            dictionary = Dictionary::load(std::slice::from_ref(fname))?;
+            Alphabet::from_words(dictionary.words())
         }
+        (None, classes) => Alphabet::from_classes(&classes.unwrap_or_else(|| {
+            vec![
+                CharClass::Lowercase,
+                CharClass::Uppercase,
+                CharClass::Digit,
+                CharClass::Symbol,
+            ]
+        })),
+    };
+    let target = f64::from(options.bits);
+    let mut generator = Generator::new(alphabet, target, options.seed)?;
+    let analyzer = Analyzer::new(dictionary, Config::new());
+    let passwords = (0..options.count)
+        .map(|_| generator.generate(&analyzer, target))
+        .collect::<Result<Vec<_>, _>>()?;
+    for generated in passwords {
+        println!(
+            "{}\t{:.1}",
+            generated.password,
+            floor_to_one_decimal(generated.entropy)
+        );
     }
+    Ok(())
+}
 
-    let mut has_valid_password = false;
-    // 分割行并添加非空密码
-    for token in line.split_whitespace() {
-        if !token.is_empty() && check_password_is_valid(token) {
-            passwords.push(token.to_string());
-            has_valid_password = true;
-        } else if !token.is_empty() {
-            log::info!("Filtered out token: '{}' from line: '{}'", token, line);
+/// 多线程时每个线程每批分到的候选密码数
+const BATCH_PER_JOB: usize = 64;
+fn process_user_input(analyzer: &Analyzer) -> UQEntropyResult<()> {
+    // json/csv 模式下标准输出只包含记录
+    let mut writer = RecordWriter::new(analyzer.config().format, io::stdout());
+    let text = writer.is_none();
+    if text {
+        print_welcome();
+    }
+
+    let input = cli::open_candidates(analyzer.config())?;
+    let jobs = analyzer.config().jobs;
+    // 单线程时逐行处理，保持交互式输出不变
+    let batch_size = if jobs <= 1 { 1 } else { jobs * BATCH_PER_JOB };
+    let mut lines = input.lines();
+    let mut count_accepted = 0;
+    loop {
+        let (batch, done) = read_batch(&mut lines, batch_size);
+        let results = analyzer.evaluate_all(&batch, jobs);
+        for (password, evaluation) in batch.iter().zip(&results) {
+            if evaluation.violations.is_empty() {
+                count_accepted += 1;
+            }
+            if !report(analyzer, writer.as_mut(), password, evaluation) {
+                return finish(analyzer, count_accepted, text);
+            }
+        }
+        if done {
+            break;
         }
     }
-    has_valid_password
+    finish(analyzer, count_accepted, text)
 }
 
-fn floor_to_one_decimal(x: f64) -> f64 {
-    (x * 10.0).floor() / 10.0
+fn print_welcome() {
+    println!("Welcome to UQEntropy!");
+    println!("Written by @yaojun.");
+    println!("Enter candidate passwords to check their strength.");
+    // flush
+    std::io::stdout().flush().unwrap();
 }
 
-/// 计算字符串中字母的数量
-fn get_letter_count(s: &str) -> i32 {
-    let mut count = 0;
-    for c in s.chars() {
-        if c.is_ascii_alphabetic() {
-            count += 1;
-        }
-    }
-    count
+/// 只有文本模式下从终端读取候选密码时才进入交互界面
+fn is_interactive(config: &Config) -> bool {
+    config.format == OutputFormat::Text && config.candidates.is_none() && io::stdin().is_terminal()
 }
 
-fn do_basic_match(password: &str, passwords: &[String], password_scale: &mut i32) -> Option<f64> {
-    for (i, pwd) in passwords.iter().enumerate() {
-        *password_scale += 1;
-        if pwd == password {
-            println!(
-                "Candidate password would be matched on guess number {}",
-                i + 1
-            );
-            std::io::stdout().flush().unwrap();
-            return Some(log2(2.0 * (i + 1) as f64));
+/// 交互界面：支持行编辑和历史记录，以 `:` 开头的行是命令
+fn run_repl(mut analyzer: Analyzer) -> UQEntropyResult<()> {
+    let Ok(mut editor) = DefaultEditor::new() else {
+        return process_user_input(&analyzer);
+    };
+    print_welcome();
+    let mut last: Option<String> = None;
+    let mut stats = Stats {
+        policy: analyzer.config().policy.is_some(),
+        ..Stats::default()
+    };
+    // Ctrl-C 和 Ctrl-D 都结束输入
+    while let Ok(line) = editor.readline("> ") {
+        let _ = editor.add_history_entry(line.as_str());
+        let input = line.trim_end();
+        if repl::is_command(input) {
+            analyzer = run_command(analyzer, input, last.as_deref(), stats);
+            continue;
+        }
+        if !check_password_is_valid(input) {
+            eprintln!("Password is invalid");
+            continue;
         }
+        let evaluation = analyzer.evaluate(input);
+        stats.checked += 1;
+        if evaluation.violations.is_empty() {
+            stats.accepted += 1;
+        }
+        report(&analyzer, None, input, &evaluation);
+        last = Some(input.to_string());
     }
-    None
+    finish(&analyzer, stats.accepted, true)
 }
-#[warn(clippy::too_many_lines)]
-fn calculate_entropy_two(password: &str, passwords: &[String], config: &Config) -> f64 {
-    log::info!("passwords size = {}", passwords.len());
-    let mut password_scale = 0;
-    if let Some(entropy) = do_basic_match(password, passwords, &mut password_scale) {
-        return entropy;
-    }
 
-    // --case
-    if config.case_sensitive {
-        if let Some(entropy) = check_case_match(password, passwords, &mut password_scale) {
-            return entropy;
+/// 执行一条交互命令；改变策略或字典时返回重建后的分析器
+fn run_command(analyzer: Analyzer, input: &str, last: Option<&str>, stats: Stats) -> Analyzer {
+    let command = match input.parse::<Command>() {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("uqentropy: {}", err);
+            return analyzer;
         }
-    }
-
-    // --digit-append
-    if config.digit_append {
-        if let Some(entropy) =
-            check_digit_append_match(password, passwords, config, &mut password_scale)
-        {
-            return entropy;
+    };
+    match command {
+        Command::Explain => match last {
+            Some(password) => explain(&analyzer, password),
+            None => eprintln!("uqentropy: no candidate to explain yet"),
+        },
+        Command::Stats => println!("{}", stats),
+        Command::Help => println!("{}", repl::HELP),
+        Command::Set(setting) => {
+            let (dictionary, mut config) = analyzer.into_parts();
+            setting.apply(&mut config);
+            return Analyzer::new(dictionary, config);
         }
+        Command::Load(fname) => return load_dictionary(analyzer, &fname),
     }
+    analyzer
+}
 
-    // --double-check
-    if config.double_check {
-        if let Some(entropy) = check_double_match(password, passwords, &mut password_scale) {
-            return entropy;
-        }
+/// :load 命令：加入一个字典，出错时只报告，不退出
+fn load_dictionary(analyzer: Analyzer, fname: &str) -> Analyzer {
+    let (mut dictionary, config) = analyzer.into_parts();
+    match Dictionary::load(&[fname.to_string()]) {
+        Ok(loaded) => dictionary.append(loaded),
+        Err(err) => print_error(&err),
     }
+    println!("Dictionary holds {} password(s)", dictionary.len());
+    Analyzer::new(dictionary, config)
+}
 
-    // --leet
-    if config.leet {
-        if let Some(entropy) = check_leet_match(password, passwords, config, &mut password_scale) {
-            return entropy;
+/// :explain 命令：逐个策略列出匹配过程，再给出完整结果
+fn explain(analyzer: &Analyzer, password: &str) {
+    println!(
+        "Character set entropy: {:.1}",
+        floor_to_one_decimal(calculate_entropy(password))
+    );
+    for step in analyzer.trace(password) {
+        match &step.report {
+            Some(found) => println!(
+                "{}: matched on guess number {} (\"{}\" from {})",
+                step.strategy,
+                found.guess_number,
+                found.word.as_deref().unwrap_or_default(),
+                found.source.as_deref().unwrap_or_default()
+            ),
+            None => println!("{}: no match in {} guesses", step.strategy, step.guesses),
         }
     }
-
-    // If no match found, return a large value
-    println!(
-        "No match would be found after checking {} passwords",
-        password_scale
+    print_evaluation(
+        &analyzer.evaluate(password),
+        analyzer.config().policy.as_ref(),
     );
-    std::io::stdout().flush().unwrap();
-    f64::MAX
 }
 
-fn check_case_match(password: &str, passwords: &[String], password_scale: &mut i32) -> Option<f64> {
-    for pwd in passwords.iter() {
-        let letter_count = get_letter_count(pwd);
-        *password_scale += 2_i32.pow(letter_count as u32) - 1;
-        if pwd.to_uppercase() == password.to_uppercase() {
-            println!(
-                "Candidate password would be matched on guess number {}",
-                *password_scale
-            );
-            std::io::stdout().flush().unwrap();
-            return Some(log2(2.0 * (*password_scale as f64)));
+/// 读取至多 `size` 个有效候选密码，无效的按顺序报告；输入结束时返回 true
+fn read_batch(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    size: usize,
+) -> (Vec<String>, bool) {
+    let mut batch = Vec::new();
+    while batch.len() < size {
+        let Some(Ok(line)) = lines.next() else {
+            return (batch, true);
+        };
+        let password = line.trim_end();
+        if !check_password_is_valid(password) {
+            eprintln!("Password is invalid");
+            continue;
         }
+        batch.push(password.to_string());
     }
-
-    None
+    (batch, false)
 }
-fn dfs(
+
+/// 输出一个候选密码的结果，写记录失败时返回 false
+fn report(
+    analyzer: &Analyzer,
+    writer: Option<&mut RecordWriter<io::Stdout>>,
     password: &str,
-    leet_map: &HashMap<char, &str>,
-    pwd_chars: &mut [char],
-    index: usize,
+    evaluation: &Evaluation,
 ) -> bool {
-    if index == pwd_chars.len() {
-        // 打印当前的字符串pwd_chars
-        log::info!(
-            "transformed pwd: {}, target password: {}",
-            pwd_chars.iter().collect::<String>(),
-            password
-        );
-        return pwd_chars.iter().collect::<String>() == password;
+    log::debug!(
+        "Evaluated {}: {:.1} bits, {}",
+        Redacted(password),
+        evaluation.entropy,
+        evaluation.rating
+    );
+    let policy = analyzer.config().policy.as_ref();
+    let Some(writer) = writer else {
+        print_evaluation(evaluation, policy);
+        return true;
+    };
+    let mut record = Record::new(password, evaluation.entropy, evaluation.report.as_ref())
+        .with_rating(evaluation.rating);
+    if analyzer.config().keyboard.is_some() {
+        record = record.with_walk(evaluation.walk.as_ref());
     }
-
-    let current_char = pwd_chars[index];
-
-    if let Some(replacements) = leet_map.get(&current_char) {
-        for leet_char in replacements.chars() {
-            let original = pwd_chars[index];
-            pwd_chars[index] = leet_char;
-
-            if dfs(password, leet_map, pwd_chars, index + 1) {
-                return true;
-            }
-
-            pwd_chars[index] = original;
-        }
+    if let Some(markov) = &evaluation.markov {
+        record = record.with_markov_guesses(markov.guesses);
     }
-
-    // 保持原始字符不变，继续搜索
-    if dfs(password, leet_map, pwd_chars, index + 1) {
-        return true;
+    if let Some(count) = evaluation.breach_count {
+        record = record.with_breach_count(count);
     }
-    false
-}
-
-fn check_leet_match(
-    password: &str,
-    passwords: &[String],
-    _config: &Config,
-    password_scale: &mut i32,
-) -> Option<f64> {
-    // 修复：使用正确的 HashMap 构造方式，移除重复键
-    let leet_map: HashMap<char, &str> = HashMap::from([
-        ('a', "4@"),
-        ('b', "68"),
-        ('e', "3"),
-        ('g', "69"),
-        ('i', "1!"),
-        ('l', "1"),
-        ('o', "0"),
-        ('s', "5$"),
-        ('t', "7+"),
-        ('x', "%"),
-        ('z', "2"),
-        // 移除了大写字母的重复映射，因为 chars() 是大小写敏感的
-        ('A', "4@"),
-        ('B', "68"),
-        ('E', "3"),
-        ('G', "69"),
-        ('I', "1!"),
-        ('L', "1"),
-        ('O', "0"),
-        ('S', "5$"),
-        ('T', "7+"),
-        ('X', "%"),
-        ('Z', "2"),
-    ]);
-
-    for pwd in passwords {
-        let mut power_one = 0;
-        let mut power_two = 0;
-        let len = pwd.len();
-
-        // 修复：使用更安全的方式遍历字符
-        for c in pwd.chars() {
-            if let Some(value) = leet_map.get(&c) {
-                match value.len() {
-                    1 => power_one += 1,
-                    _ => power_two += 1,
-                }
-            }
-        }
-
-        if power_one + power_two == 0 {
-            continue;
-        }
-
-        // 修复：避免整数溢出，使用 checked_pow
-        let scale_increment = 2_i32
-            .checked_pow(power_one as u32)
-            .and_then(|x| x.checked_mul(3_i32.checked_pow(power_two as u32)?))
-            .and_then(|x| x.checked_sub(1))
-            .unwrap_or(i32::MAX); // 处理溢出情况
-
-        *password_scale = password_scale.saturating_add(scale_increment);
-        log::info!("current scale: {}, pwd: {}", *password_scale, pwd);
-        if len != password.len() {
-            continue;
-        }
-
-        // 将字典密码转换为可变的char数组，以便进行leet变换
-        let mut pwd_chars: Vec<char> = pwd.chars().collect();
-        log::info!("dict pwd: {}", pwd);
-
-        // 调用dfs函数，将字典密码进行leet变换，然后与目标密码比较
-        if dfs(password, &leet_map, &mut pwd_chars, 0) {
-            println!(
-                "Candidate password would be matched on guess number {}",
-                *password_scale
-            );
-            std::io::stdout().flush().unwrap();
-            return Some(log2(2.0 * (*password_scale as f64)));
-        }
+    if policy.is_some() {
+        record = record.with_violations(&evaluation.violations);
     }
-    None
+    writer.write(&record).is_ok()
 }
 
-fn check_digit_append_match(
-    password: &str,
-    passwords: &[String],
-    config: &Config,
-    password_scale: &mut i32,
-) -> Option<f64> {
-    let power_table = [10, 100, 1000, 10000, 100000, 1000000, 10000000];
-
-    for pwd in passwords.iter() {
-        let last_char = pwd.chars().last()?;
-        if !last_char.is_ascii_digit() {
-            for (j, _) in power_table.iter().enumerate().take(config.num_digits) {
-                for value in 0..power_table[j] {
-                    let digit_append = format!("{:0width$}", value, width = j + 1);
-                    let new_pwd = format!("{}{}", pwd, digit_append);
-                    *password_scale += 1;
-                    if new_pwd == password {
-                        println!(
-                            "Candidate password would be matched on guess number {}",
-                            *password_scale
-                        );
-                        return Some(log2(2.0 * (*password_scale as f64)));
-                    }
-                }
-            }
-        }
+/// 文本模式下打印一个候选密码的全部结果
+fn print_evaluation(evaluation: &Evaluation, policy: Option<&Policy>) {
+    if let Some(found) = &evaluation.report {
+        print_match(found);
+    }
+    if let Some(walk) = &evaluation.walk {
+        println!(
+            "Candidate password is a keyboard walk on the {} layout ({} turn{})",
+            walk.layout,
+            walk.turns,
+            if walk.turns == 1 { "" } else { "s" }
+        );
     }
-    None
-}
-
-fn check_double_match(
-    password: &str,
-    passwords: &[String],
-    password_scale: &mut i32,
-) -> Option<f64> {
-    for first in passwords.iter() {
-        let len1 = first.len();
-        if len1 > password.len() || !password.starts_with(first) {
-            *password_scale += passwords.len() as i32;
-            continue;
-        }
-        for second in passwords.iter() {
-            *password_scale += 1;
-
-            let len2 = second.len();
-            if len1 + len2 != password.len() {
-                continue;
-            }
-            let new_pwd = format!("{}{}", first, second);
-            if new_pwd == password {
-                println!(
-                    "Candidate password would be matched on guess number {}",
-                    *password_scale
-                );
-                return Some(log2(2.0 * (*password_scale as f64)));
-            }
+    if let Some(decomposition) = &evaluation.decomposition {
+        print_decomposition(decomposition);
+    }
+    if let Some(markov) = &evaluation.markov {
+        println!("Markov model estimates {:.0} guesses", markov.guesses);
+    }
+    if let Some(count) = evaluation.breach_count.filter(|&count| count > 0) {
+        println!(
+            "Candidate password appears {} time(s) in the breach corpus",
+            count
+        );
+    }
+    println!(
+        "Password entropy calculated to be {:.1}",
+        floor_to_one_decimal(evaluation.entropy)
+    );
+    println!("Password strength rating: {}", evaluation.rating);
+    if policy.is_some() {
+        for violation in &evaluation.violations {
+            println!("Policy violation: {}", violation);
         }
     }
-    None
+    std::io::stdout().flush().unwrap();
 }
 
-fn main() {
-    // 初始化日志系统
-    init_logging();
-
-    let args: Vec<String> = env::args().collect();
-    // 把错误传播到最外层，统一处理
-    let (config, filenames, file_present) = parse_arguments(&args);
-
-    // 当使用选项时必须提供文件
-    if (config.leet || config.case_sensitive || config.digit_append || config.double_check)
-        && filenames.is_empty()
-    {
-        eprintln!("{}", USAGE_MSG);
-        exit(ExitCodes::Usage as i32);
+/// 没有候选密码满足策略时返回 [`UQEntropyError::NoStrong`]
+fn finish(analyzer: &Analyzer, count_accepted: usize, text: bool) -> UQEntropyResult<()> {
+    if count_accepted > 0 {
+        return Ok(());
     }
-
-    let mut passwords: Vec<String> = Vec::new();
-    if !filenames.is_empty() {
-        read_file(&filenames, &mut passwords, &config);
+    if text && analyzer.config().policy.is_some() {
+        println!("No password(s) meeting the policy have been identified");
+    } else if text {
+        println!("{}", UQEntropyError::NoStrong);
     }
-
-    process_user_input(&config, &passwords, file_present);
+    std::io::stdout().flush().unwrap();
+    Err(UQEntropyError::NoStrong)
 }
 
-// 这里返回的是三元组
-fn parse_arguments(args: &[String]) -> (Config, Vec<String>, bool) {
-    let mut filenames = Vec::new();
-    let mut config = Config::new();
-
-    let mut i = 1;
-    let mut file_present = false;
-    while i < args.len() {
-        // 这里 match用得妙呀
-        match args[i].as_str() {
-            "--leet" => config.leet = true,
-            "--case" => config.case_sensitive = true,
-            "--double" => config.double_check = true,
-            "--digit-append" => {
-                if i + 1 >= args.len() {
-                    eprintln!("{}", USAGE_MSG);
-                    exit(ExitCodes::Usage as i32);
-                }
-                config.num_digits = args[i + 1].parse::<usize>().unwrap_or_else(|_| {
-                    eprintln!("{}", USAGE_MSG);
-                    exit(ExitCodes::Usage as i32);
-                });
-                if config.num_digits < 1 || config.num_digits > 8 {
-                    eprintln!("{}", USAGE_MSG);
-                    exit(ExitCodes::Usage as i32);
-                }
-                config.digit_append = true;
-                i += 1;
-            }
-            _ => {
-                // if args is empty should exit
-                if args[i].is_empty() {
-                    eprintln!("{}", USAGE_MSG);
-                    exit(ExitCodes::Usage as i32);
-                }
-                if args[i].starts_with('-') {
-                    eprintln!("{}", USAGE_MSG);
-                    exit(ExitCodes::Usage as i32);
-                }
-                for arg in args.iter().skip(i) {
-                    filenames.push(arg.clone());
-                }
-                file_present = true;
-                break;
-            }
-        }
-        i += 1;
+fn print_match(report: &MatchReport) {
+    if report.is_match() {
+        println!(
+            "Candidate password would be matched on guess number {}",
+            report.guess_number
+        );
+    } else {
+        println!(
+            "No match would be found after checking {} passwords",
+            report.guess_number
+        );
     }
-    (config, filenames, file_present)
 }
 
-fn process_user_input(config: &Config, passwords: &[String], file_present: bool) {
-    println!("Welcome to UQEntropy!");
-    println!("Written by @yaojun.");
-    println!("Enter candidate passwords to check their strength.");
-    // flush
-    std::io::stdout().flush().unwrap();
-
-    let stdin = io::stdin();
-    let mut count_strong = 0;
-
-    for line_result in stdin.lock().lines() {
-        match line_result {
-            Ok(line) => {
-                let password = line.trim_end();
-
-                if !check_password_is_valid(password) {
-                    eprintln!("Password is invalid");
-                    continue;
-                }
-
-                let mut entropy = calculate_entropy(password);
-                if file_present {
-                    let entropy_two = calculate_entropy_two(password, passwords, config);
-                    if entropy_two < entropy {
-                        entropy = entropy_two;
-                    }
-                }
-                if entropy >= 60.0 {
-                    count_strong += 1;
-                }
-                println!(
-                    "Password entropy calculated to be {:.1}",
-                    floor_to_one_decimal(entropy)
-                );
-                println!("Password strength rating: {}", map_to_strength(entropy));
-                std::io::stdout().flush().unwrap();
-            }
-            Err(_) => {
-                break;
-            }
-        }
-    }
-    if count_strong == 0 {
-        println!("No strong password(s) have been identified");
-        std::io::stdout().flush().unwrap();
-        exit(ExitCodes::NoStrong as i32)
-    } else {
-        exit(0);
+fn print_decomposition(decomposition: &Decomposition) {
+    println!(
+        "Candidate password decomposes into {} segment{} ({:.0} guesses)",
+        decomposition.segments.len(),
+        if decomposition.segments.len() == 1 {
+            ""
+        } else {
+            "s"
+        },
+        decomposition.guesses
+    );
+    for segment in &decomposition.segments {
+        println!(
+            "  \"{}\" {}: {:.0} guesses",
+            segment.token, segment.kind, segment.guesses
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn test_calculate_entropy() {
-        assert_eq!(calculate_entropy("password"), 37.6);
-    }
 
     #[test]
-    fn test_calculate_entropy_two() {
-        let passwords = vec!["<PASSWORD>".to_string(), "123456".to_string()];
-        assert_eq!(
-            calculate_entropy_two("password", &passwords, &Config::new()),
-            f64::MAX
-        );
+    fn test_generate_exit_codes() {
+        let too_few = UQEntropyError::Generate(GenerateError::TooFewWords);
+        assert_eq!(ExitCodes::from(&too_few), ExitCodes::InvalidFile);
+        let unreachable = UQEntropyError::Generate(GenerateError::Unreachable(80.0));
+        assert_eq!(ExitCodes::from(&unreachable), ExitCodes::NoStrong);
     }
 }
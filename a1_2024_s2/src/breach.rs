@@ -0,0 +1,73 @@
+//! Lookups in a local copy of a breached-password corpus.
+//!
+//! The corpus is laid out like the Pwned Passwords range API: the SHA-1 of
+//! every password is split after five hex digits, and the file named by the
+//! prefix (`5BAA6` or `5BAA6.txt`) holds one `SUFFIX:COUNT` line per
+//! password. Only that one small file is read for a candidate.
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Hex digits of the hash that name the range file.
+const PREFIX_LEN: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct BreachDb {
+    dir: PathBuf,
+}
+
+impl BreachDb {
+    /// Fails unless `dir` is a readable directory.
+    pub fn open(dir: &str) -> io::Result<Self> {
+        fs::read_dir(dir)?;
+        Ok(BreachDb { dir: dir.into() })
+    }
+
+    /// Upper-case hex SHA-1 of `password`, as used in the range files.
+    pub fn hash(password: &str) -> String {
+        Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect()
+    }
+
+    /// Times `password` appears in the corpus; zero when its range file is
+    /// missing or does not list it.
+    pub fn count(&self, password: &str) -> u64 {
+        let hash = Self::hash(password);
+        let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+        let Some(range) = [prefix.to_string(), format!("{}.txt", prefix)]
+            .iter()
+            .find_map(|name| fs::read_to_string(self.dir.join(name)).ok())
+        else {
+            return 0;
+        };
+        range
+            .lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .find(|(s, _)| s.eq_ignore_ascii_case(suffix))
+            .and_then(|(_, count)| count.trim().parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_lookup() {
+        assert_eq!(
+            BreachDb::hash("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+        let db = BreachDb::open("testfiles/breach").unwrap();
+        assert_eq!(db.count("password"), 9545824);
+        assert_eq!(db.count("CorrectHorseBatteryStaple1!"), 3);
+        // 前缀文件存在但没有该后缀，或前缀文件不存在
+        assert_eq!(db.count("passw0rd"), 0);
+        assert_eq!(db.count("hunter2"), 0);
+        assert!(BreachDb::open("testfiles/no_such_dir").is_err());
+    }
+}
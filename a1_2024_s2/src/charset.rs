@@ -0,0 +1,140 @@
+//! Character classes used by [`crate::calculate_entropy`].
+//!
+//! Lengths are counted in grapheme clusters, so "é" typed as `e` plus a
+//! combining accent is one character, and every cluster is classed by its
+//! first code point. ASCII keeps the classic pools (10 digits, 26 lower, 26
+//! upper, 32 symbols); other scripts get a pool of their own, both cases
+//! together, sized by the characters in everyday use rather than everything
+//! Unicode assigns to the script.
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Charset {
+    Digit,
+    Lower,
+    Upper,
+    Symbol,
+    /// Accented Latin letters such as "é" or "ß".
+    LatinExtended,
+    Greek,
+    Cyrillic,
+    /// Chinese characters (also Japanese kanji).
+    Han,
+    /// Japanese hiragana and katakana.
+    Kana,
+    /// Korean syllables and jamo.
+    Hangul,
+    /// Letters of any other script.
+    OtherLetter,
+    /// Non-ASCII punctuation, symbols and emoji.
+    OtherSymbol,
+}
+
+impl Charset {
+    /// Class of a grapheme cluster, taken from its first code point.
+    pub fn of(grapheme: &str) -> Charset {
+        let Some(c) = grapheme.chars().next() else {
+            return Charset::Symbol;
+        };
+        if c.is_ascii() {
+            return if c.is_ascii_lowercase() {
+                Charset::Lower
+            } else if c.is_ascii_uppercase() {
+                Charset::Upper
+            } else if c.is_ascii_digit() {
+                Charset::Digit
+            } else {
+                Charset::Symbol
+            };
+        }
+        match c {
+            '\u{C0}'..='\u{24F}' if c.is_alphabetic() => Charset::LatinExtended,
+            '\u{370}'..='\u{3FF}' => Charset::Greek,
+            '\u{400}'..='\u{52F}' => Charset::Cyrillic,
+            '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{20000}'..='\u{2FA1F}' => Charset::Han,
+            '\u{3040}'..='\u{30FF}' => Charset::Kana,
+            '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+                Charset::Hangul
+            }
+            _ if c.is_alphabetic() => Charset::OtherLetter,
+            _ => Charset::OtherSymbol,
+        }
+    }
+
+    /// Number of characters an attacker tries for this class.
+    pub fn size(&self) -> u32 {
+        match self {
+            Charset::Digit => 10,
+            Charset::Lower | Charset::Upper => 26,
+            Charset::Symbol => 32,
+            Charset::LatinExtended => 64,
+            Charset::Greek => 48,
+            Charset::Cyrillic => 66,
+            // 《通用规范汉字表》一级字表
+            Charset::Han => 3500,
+            Charset::Kana => 169,
+            // KS X 1001 中的常用音节
+            Charset::Hangul => 2350,
+            Charset::OtherLetter | Charset::OtherSymbol => 100,
+        }
+    }
+}
+
+/// Length in grapheme clusters.
+pub fn grapheme_len(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+/// NFKC form used whenever a candidate is compared with dictionary words,
+/// so that e.g. full-width "ｐａｓｓ" matches "pass". ASCII is left as is.
+pub fn normalize(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(s.nfkc().collect())
+    }
+}
+
+/// Characters accepted in candidates and dictionary files: printable ASCII
+/// and ASCII whitespace, or any non-ASCII character that is not a control
+/// character.
+pub fn is_allowed(c: char) -> bool {
+    if c.is_ascii() {
+        c.is_ascii_graphic() || c.is_ascii_whitespace()
+    } else {
+        !c.is_control()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(Charset::of("a"), Charset::Lower);
+        assert_eq!(Charset::of("~"), Charset::Symbol);
+        assert_eq!(Charset::of("é"), Charset::LatinExtended);
+        assert_eq!(Charset::of("e\u{301}"), Charset::Lower);
+        assert_eq!(Charset::of("Ж"), Charset::Cyrillic);
+        assert_eq!(Charset::of("密"), Charset::Han);
+        assert_eq!(Charset::of("カ"), Charset::Kana);
+        assert_eq!(Charset::of("한"), Charset::Hangul);
+        assert_eq!(Charset::of("€"), Charset::OtherSymbol);
+    }
+
+    #[test]
+    fn test_length_and_normalization() {
+        assert_eq!(grapheme_len("e\u{301}t\u{e9}"), 3);
+        assert_eq!(grapheme_len("密码"), 2);
+        assert_eq!(normalize("ｐａｓｓ１"), "pass1");
+        assert!(matches!(normalize("pass"), Cow::Borrowed(_)));
+        assert!(is_allowed('密') && is_allowed(' '));
+        assert!(!is_allowed('\u{1}') && !is_allowed('\u{85}'));
+    }
+}
@@ -0,0 +1,448 @@
+//! Command-line parsing for `uqentropy` and its subcommands.
+//!
+//! Every bad command line is a [`UQEntropyError::Usage`] carrying the usage
+//! line to print, so each failure can be checked without running the binary.
+use crate::breach::BreachDb;
+use crate::error::{UQEntropyError, UQEntropyResult};
+use crate::markov::{MarkovError, MarkovModel};
+use crate::policy::{CharClass, Policy, PolicyError};
+use crate::strategy::{LeetTable, LeetTableError};
+use crate::Config;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+pub const USAGE_MSG: &str =
+    "Usage: ./uqentropy [--leet] [--double] [--digit-append 1..8] [--case] [listfilename ...]";
+pub const BUILD_USAGE_MSG: &str = "Usage: ./uqentropy build-dict listfilename ... -o output.uqd";
+pub const TRAIN_USAGE_MSG: &str = "Usage: ./uqentropy train listfilename ... -o model.bin";
+pub const LINT_USAGE_MSG: &str = "Usage: ./uqentropy lint listfilename ...";
+pub const GENERATE_USAGE_MSG: &str = "Usage: ./uqentropy generate --bits 1..1024 [--words listfilename | --charset class,...] [--count n] [--seed n]";
+
+/// Printed by `./uqentropy --help`. The usage lines above stay as they are,
+/// so this is where every option is listed.
+pub const HELP_MSG: &str = "\
+Usage: ./uqentropy [options] [listfilename ...]
+Checks candidate passwords, one per line, against the word lists.
+
+Matching:
+  --leet                  try leet substitutions
+  --leet-table FILE       load leet substitutions from FILE (implies --leet)
+  --case                  try upper-case variants
+  --double                try words repeated twice
+  --digit-append 1..8     try words followed by up to N digits
+  --dates                 try year and date prefixes and suffixes
+  --combine               chain case, leet and digit-append mutations
+  --keyboard LAYOUT       detect keyboard walks on qwerty, azerty or dvorak
+  --decompose             split candidates into patterns
+  --markov FILE           estimate guesses with a model made by train
+  --breach-db DIR         look candidates up in a breached-password corpus
+  --policy FILE           rate and accept candidates by a TOML policy
+
+Input and output:
+  --candidates FILE       read candidates from FILE instead of standard input
+  --jobs N                evaluate candidates on N threads
+  --format FORMAT         print text, json or csv
+  --log-file FILE         write a log to FILE
+  --log-level LEVEL       log at off, error, warn, info, debug or trace
+
+Subcommands:
+  ./uqentropy build-dict listfilename ... -o output.uqd
+  ./uqentropy train listfilename ... -o model.bin
+  ./uqentropy generate --bits 1..1024 [--words listfilename | --charset class,...] [--count n] [--seed n]
+  ./uqentropy lint listfilename ...
+";
+
+/// Options of the `generate` subcommand.
+#[derive(Debug)]
+pub struct GenerateOptions {
+    pub bits: u32,
+    pub words: Option<String>,
+    pub classes: Option<Vec<CharClass>>,
+    pub count: usize,
+    pub seed: Option<u64>,
+}
+
+/// Word lists to check with `lint`; options are not accepted.
+pub fn lint_args(args: &[String]) -> UQEntropyResult<&[String]> {
+    if args.is_empty()
+        || args
+            .iter()
+            .any(|arg| arg.is_empty() || arg.starts_with('-'))
+    {
+        return Err(UQEntropyError::Usage(LINT_USAGE_MSG));
+    }
+    Ok(args)
+}
+
+/// Options of `generate`; `--bits` is required and `--words` excludes
+/// `--charset`.
+pub fn generate_options(args: &[String]) -> UQEntropyResult<GenerateOptions> {
+    let usage = || UQEntropyError::Usage(GENERATE_USAGE_MSG);
+    let mut options = GenerateOptions {
+        bits: 0,
+        words: None,
+        classes: None,
+        count: 1,
+        seed: None,
+    };
+    let mut iter = args.iter();
+    while let Some(option) = iter.next() {
+        let value = iter.next().ok_or_else(usage)?;
+        match option.as_str() {
+            "--bits" => {
+                options.bits = value
+                    .parse()
+                    .ok()
+                    .filter(|b| (1..=1024).contains(b))
+                    .ok_or_else(usage)?
+            }
+            "--words" if options.words.is_none() => options.words = Some(value.clone()),
+            "--charset" if options.classes.is_none() => {
+                let classes: Result<Vec<CharClass>, _> = value.split(',').map(str::parse).collect();
+                options.classes = Some(classes.map_err(|_| usage())?);
+            }
+            "--count" => {
+                options.count = value.parse().ok().filter(|&c| c >= 1).ok_or_else(usage)?
+            }
+            "--seed" => options.seed = Some(value.parse().map_err(|_| usage())?),
+            _ => return Err(usage()),
+        }
+    }
+    if options.bits == 0 || (options.words.is_some() && options.classes.is_some()) {
+        return Err(usage());
+    }
+    Ok(options)
+}
+
+/// Arguments of `build-dict` and `train`: at least one input file and the
+/// output file given with `-o`. `usage` is the usage line of the command.
+pub fn subcommand_args(
+    args: &[String],
+    usage: &'static str,
+) -> UQEntropyResult<(Vec<String>, String)> {
+    let mut filenames = Vec::new();
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-o" && output.is_none() {
+            output = iter.next();
+        } else if arg.is_empty() || arg.starts_with('-') {
+            return Err(UQEntropyError::Usage(usage));
+        } else {
+            filenames.push(arg.clone());
+        }
+    }
+    match (output, filenames.is_empty()) {
+        (Some(output), false) => Ok((filenames, output.clone())),
+        _ => Err(UQEntropyError::Usage(usage)),
+    }
+}
+
+/// Options and word lists of a check run; `args[0]` is the program name.
+/// Files named by options are loaded here, so their errors surface too.
+pub fn parse_arguments(args: &[String]) -> UQEntropyResult<(Config, Vec<String>)> {
+    let mut filenames = Vec::new();
+    let mut config = Config::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        // 这里 match用得妙呀
+        match args[i].as_str() {
+            "--leet" => config.leet = true,
+            "--case" => config.case_sensitive = true,
+            "--double" => config.double_check = true,
+            "--dates" => config.dates = true,
+            "--decompose" => config.decompose = true,
+            "--combine" => config.combine = true,
+            option if parse_value_option(&mut config, option, args, i)? => i += 1,
+            _ => {
+                // 空参数或未知选项都是用法错误
+                if args[i].is_empty() || args[i].starts_with('-') {
+                    return Err(UQEntropyError::Usage(USAGE_MSG));
+                }
+                for arg in args.iter().skip(i) {
+                    filenames.push(arg.clone());
+                }
+                break;
+            }
+        }
+        i += 1;
+    }
+
+    // 当使用选项时必须提供文件
+    if (config.leet
+        || config.case_sensitive
+        || config.digit_append
+        || config.double_check
+        || config.dates
+        || config.combine)
+        && filenames.is_empty()
+    {
+        return Err(UQEntropyError::Usage(USAGE_MSG));
+    }
+    Ok((config, filenames))
+}
+
+// Options taking a value; false if `option` is not one of them
+fn parse_value_option(
+    config: &mut Config,
+    option: &str,
+    args: &[String],
+    i: usize,
+) -> UQEntropyResult<bool> {
+    match option {
+        "--format" => config.format = option_value(args, i)?,
+        "--leet-table" => {
+            config.leet_table = Some(read_leet_table(&option_value::<String>(args, i)?)?);
+            config.leet = true;
+        }
+        "--policy" => config.policy = Some(read_policy(&option_value::<String>(args, i)?)?),
+        "--markov" => config.markov = Some(read_markov(&option_value::<String>(args, i)?)?),
+        "--breach-db" => {
+            config.breach_db = Some(read_breach_db(&option_value::<String>(args, i)?)?)
+        }
+        "--log-file" => config.log_file = Some(option_value(args, i)?),
+        "--log-level" => config.log_level = Some(option_value(args, i)?),
+        "--candidates" => config.candidates = Some(option_value(args, i)?),
+        "--jobs" => config.jobs = bounded_value(args, i, 1..=usize::MAX)?,
+        "--keyboard" => config.keyboard = Some(option_value(args, i)?),
+        "--digit-append" => {
+            config.num_digits = bounded_value(args, i, 1..=8)?;
+            config.digit_append = true;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+// An option file that cannot be opened is unreadable, any other error makes
+// it an invalid `kind`
+fn file_error(fname: &str, kind: &str, unreadable: bool, err: impl Display) -> UQEntropyError {
+    if unreadable {
+        UQEntropyError::UnreadableFile(fname.to_string())
+    } else {
+        UQEntropyError::InvalidFile(format!("invalid {} \"{}\" ({})", kind, fname, err))
+    }
+}
+
+fn read_leet_table(fname: &str) -> UQEntropyResult<LeetTable> {
+    LeetTable::load(fname).map_err(|err| {
+        let unreadable = matches!(err, LeetTableError::Io(_));
+        file_error(fname, "leet table", unreadable, err)
+    })
+}
+
+fn read_policy(fname: &str) -> UQEntropyResult<Policy> {
+    Policy::load(fname).map_err(|err| {
+        let unreadable = matches!(err, PolicyError::Io(_));
+        file_error(fname, "policy file", unreadable, err)
+    })
+}
+
+fn read_markov(fname: &str) -> UQEntropyResult<MarkovModel> {
+    MarkovModel::load(fname).map_err(|err| {
+        let unreadable = matches!(err, MarkovError::Io(_));
+        file_error(fname, "model file", unreadable, err)
+    })
+}
+
+fn read_breach_db(dirname: &str) -> UQEntropyResult<BreachDb> {
+    BreachDb::open(dirname).map_err(|_| UQEntropyError::UnreadableFile(dirname.to_string()))
+}
+
+// The value after option `i`; a missing or unparsable one is a usage error
+fn option_value<T: FromStr>(args: &[String], i: usize) -> UQEntropyResult<T> {
+    args.get(i + 1)
+        .and_then(|value| value.parse().ok())
+        .ok_or(UQEntropyError::Usage(USAGE_MSG))
+}
+
+// Like option_value, but the value must also lie in `range`
+fn bounded_value<T: FromStr + PartialOrd>(
+    args: &[String],
+    i: usize,
+    range: RangeInclusive<T>,
+) -> UQEntropyResult<T> {
+    let value = option_value(args, i)?;
+    if !range.contains(&value) {
+        return Err(UQEntropyError::Usage(USAGE_MSG));
+    }
+    Ok(value)
+}
+
+/// Candidates come from the `--candidates` file, otherwise from standard
+/// input.
+pub fn open_candidates(config: &Config) -> UQEntropyResult<Box<dyn BufRead>> {
+    let Some(fname) = &config.candidates else {
+        return Ok(Box::new(BufReader::new(io::stdin())));
+    };
+    let file = File::open(fname).map_err(|_| UQEntropyError::UnreadableFile(fname.clone()))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn usage<T>(result: UQEntropyResult<T>) -> &'static str {
+        match result {
+            Err(UQEntropyError::Usage(usage)) => usage,
+            Err(err) => panic!("expected a usage error, got {:?}", err),
+            Ok(_) => panic!("expected a usage error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let (config, filenames) =
+            parse_arguments(&strings(&["uqentropy", "--leet", "--jobs", "4", "a", "b"])).unwrap();
+        assert!(config.leet);
+        assert_eq!(config.jobs, 4);
+        assert_eq!(filenames, ["a", "b"]);
+        assert!(parse_arguments(&strings(&["uqentropy"])).is_ok());
+    }
+
+    #[test]
+    fn test_parse_arguments_usage() {
+        for args in [
+            &["--bogus", "list"][..],
+            &["", "list"],
+            &["--format"],
+            &["--format", "xml", "list"],
+            &["--jobs", "0", "list"],
+            &["--digit-append", "9", "list"],
+            &["--digit-append", "two", "list"],
+            &["--keyboard", "nope", "list"],
+            &["--log-level", "loud", "list"],
+            &["--leet-table"],
+            // 使用选项时必须提供文件
+            &["--leet"],
+            &["--case"],
+            &["--double"],
+            &["--dates"],
+            &["--combine"],
+            &["--digit-append", "2"],
+        ] {
+            let args: Vec<String> = std::iter::once("uqentropy".to_string())
+                .chain(strings(args))
+                .collect();
+            assert_eq!(usage(parse_arguments(&args)), USAGE_MSG, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_help_lists_every_option() {
+        for option in [
+            "--leet",
+            "--leet-table",
+            "--case",
+            "--double",
+            "--digit-append",
+            "--dates",
+            "--combine",
+            "--keyboard",
+            "--decompose",
+            "--markov",
+            "--breach-db",
+            "--policy",
+            "--candidates",
+            "--jobs",
+            "--format",
+            "--log-file",
+            "--log-level",
+            "build-dict",
+            "train",
+            "generate",
+            "lint",
+        ] {
+            assert!(HELP_MSG.contains(option), "{}", option);
+        }
+    }
+
+    #[test]
+    fn test_option_files() {
+        for option in ["--policy", "--leet-table", "--markov", "--breach-db"] {
+            let args = strings(&["uqentropy", option, "no/such/file", "list"]);
+            assert!(
+                matches!(
+                    parse_arguments(&args),
+                    Err(UQEntropyError::UnreadableFile(f)) if f == "no/such/file"
+                ),
+                "{}",
+                option
+            );
+        }
+        let config = Config {
+            candidates: Some("no/such/file".to_string()),
+            ..Config::new()
+        };
+        assert!(matches!(
+            open_candidates(&config),
+            Err(UQEntropyError::UnreadableFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_options() {
+        let options =
+            generate_options(&strings(&["--bits", "80", "--count", "3", "--seed", "7"])).unwrap();
+        assert_eq!(
+            (options.bits, options.count, options.seed),
+            (80, 3, Some(7))
+        );
+
+        for args in [
+            &[][..],
+            &["--count", "2"],
+            &["--bits"],
+            &["--bits", "0"],
+            &["--bits", "1025"],
+            &["--bits", "80", "--count", "0"],
+            &["--bits", "80", "--seed", "-1"],
+            &["--bits", "80", "--charset", "digit,emoji"],
+            &["--bits", "80", "--words", "a", "--charset", "digit"],
+            &["--bits", "80", "--words", "a", "--words", "b"],
+            &["--bits", "80", "--bogus", "1"],
+        ] {
+            let result = generate_options(&strings(args));
+            assert_eq!(usage(result), GENERATE_USAGE_MSG, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_subcommand_args() {
+        let (filenames, output) =
+            subcommand_args(&strings(&["a", "-o", "out", "b"]), BUILD_USAGE_MSG).unwrap();
+        assert_eq!(
+            (filenames, output),
+            (strings(&["a", "b"]), "out".to_string())
+        );
+
+        for args in [
+            &["a"][..],
+            &["-o", "out"],
+            &["a", "-o"],
+            &["a", "-o", "out", "-o", "again"],
+            &["a", "-x", "-o", "out"],
+            &["", "-o", "out"],
+        ] {
+            let result = subcommand_args(&strings(args), TRAIN_USAGE_MSG);
+            assert_eq!(usage(result), TRAIN_USAGE_MSG, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_lint_args() {
+        assert!(lint_args(&strings(&["a", "b"])).is_ok());
+        for args in [&[][..], &["a", "-v"], &["a", ""]] {
+            assert_eq!(usage(lint_args(&strings(args))), LINT_USAGE_MSG);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! zxcvbn-style decomposition of a candidate into pattern segments.
+//!
+//! Every substring is checked against the known patterns (dictionary word,
+//! repeat, sequence, date, keyboard walk). A dynamic programme then picks the
+//! split with the fewest total guesses, where the total is the product of the
+//! segment guesses times `l!` for `l` segments (the cracker also has to guess
+//! the order of the patterns). Anything not covered by a pattern is a
+//! brute-force segment costed with [`calculate_entropy`]; two brute-force
+//! segments are never adjacent, so a gap is always costed as a whole.
+//!
+//! The programme is cubic in the length, so as in zxcvbn only the first
+//! [`MAX_ANALYSED_LEN`] characters are analysed; the rest is brute force.
+use crate::keyboard::{Keyboard, Layout};
+use crate::strategy::DateStrategy;
+use crate::{calculate_entropy, log2};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Shortest repeat or sequence worth reporting.
+const MIN_PATTERN_LEN: usize = 3;
+
+/// Characters searched for patterns; any after these are brute force.
+pub const MAX_ANALYSED_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Dictionary,
+    Repeat,
+    Sequence,
+    Date,
+    KeyboardWalk,
+    BruteForce,
+}
+
+impl fmt::Display for SegmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SegmentKind::Dictionary => "dictionary",
+            SegmentKind::Repeat => "repeat",
+            SegmentKind::Sequence => "sequence",
+            SegmentKind::Date => "date",
+            SegmentKind::KeyboardWalk => "keyboard walk",
+            SegmentKind::BruteForce => "brute force",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One piece of a decomposed password.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub token: String,
+    pub kind: SegmentKind,
+    /// Guesses needed for this segment on its own.
+    pub guesses: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decomposition {
+    pub segments: Vec<Segment>,
+    /// Guesses for the whole password, including the `l!` ordering factor.
+    pub guesses: f64,
+}
+
+impl Decomposition {
+    /// Entropy in the same terms as a dictionary match on guess `guesses`.
+    pub fn entropy(&self) -> f64 {
+        log2(2.0 * self.guesses)
+    }
+}
+
+/// Holds the lookup tables the pattern matchers need.
+pub struct Decomposer {
+    ranks: HashMap<String, u64>,
+    dates: DateStrategy,
+    keyboard: Keyboard,
+}
+
+fn brute_force_guesses(token: &str) -> f64 {
+    calculate_entropy(token).exp2().max(1.0)
+}
+
+/// Guesses for an ascending or descending run like "abcd" or "9876".
+fn sequence_guesses(chars: &[char]) -> Option<f64> {
+    if chars.len() < MIN_PATTERN_LEN {
+        return None;
+    }
+    let same_class = |c: &char| {
+        (c.is_ascii_lowercase() && chars[0].is_ascii_lowercase())
+            || (c.is_ascii_uppercase() && chars[0].is_ascii_uppercase())
+            || (c.is_ascii_digit() && chars[0].is_ascii_digit())
+    };
+    if !chars.iter().all(same_class) {
+        return None;
+    }
+    let delta = chars[1] as i32 - chars[0] as i32;
+    if delta.abs() != 1 || chars.windows(2).any(|w| w[1] as i32 - w[0] as i32 != delta) {
+        return None;
+    }
+    let start = if "aAzZ019".contains(chars[0]) {
+        4.0
+    } else if chars[0].is_ascii_digit() {
+        10.0
+    } else {
+        26.0
+    };
+    let direction = if delta < 0 { 2.0 } else { 1.0 };
+    Some(start * chars.len() as f64 * direction)
+}
+
+type Table = Vec<Vec<[f64; 2]>>;
+type Backpointers = Vec<Vec<[(usize, usize, Option<(SegmentKind, f64)>); 2]>>;
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+impl Decomposer {
+    pub fn new(passwords: &[String], layout: Layout) -> Self {
+        let mut ranks = HashMap::new();
+        for (i, pwd) in passwords.iter().enumerate() {
+            ranks.entry(pwd.clone()).or_insert(i as u64 + 1);
+        }
+        Decomposer {
+            ranks,
+            dates: DateStrategy::new(),
+            keyboard: Keyboard::new(layout),
+        }
+    }
+
+    /// Cheapest non-repeat pattern for `chars`.
+    fn simple_pattern(&self, chars: &[char]) -> Option<(SegmentKind, f64)> {
+        let token: String = chars.iter().collect();
+        let mut candidates = Vec::new();
+        if let Some(rank) = self.ranks.get(&token) {
+            candidates.push((SegmentKind::Dictionary, *rank as f64));
+        }
+        if let Some(rank) = self.dates.stamp_rank(&token) {
+            candidates.push((SegmentKind::Date, rank as f64));
+        }
+        if let Some(guesses) = sequence_guesses(chars) {
+            candidates.push((SegmentKind::Sequence, guesses));
+        }
+        if let Some(walk) = self.keyboard.find_walk(&token) {
+            candidates.push((SegmentKind::KeyboardWalk, walk.guesses));
+        }
+        candidates.into_iter().min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// "abcabc" is "abc" guessed once and then repeated.
+    fn repeat_pattern(&self, chars: &[char]) -> Option<f64> {
+        if chars.len() < MIN_PATTERN_LEN {
+            return None;
+        }
+        (1..=chars.len() / 2)
+            .filter(|base| chars.len().is_multiple_of(*base))
+            .find(|&base| chars.chunks(base).all(|chunk| chunk == &chars[..base]))
+            .map(|base| {
+                let token: String = chars[..base].iter().collect();
+                let base_guesses = self
+                    .simple_pattern(&chars[..base])
+                    .map_or(f64::MAX, |(_, guesses)| guesses)
+                    .min(brute_force_guesses(&token));
+                base_guesses * (chars.len() / base) as f64
+            })
+    }
+
+    fn best_pattern(&self, chars: &[char]) -> Option<(SegmentKind, f64)> {
+        let simple = self.simple_pattern(chars);
+        let repeat = self
+            .repeat_pattern(chars)
+            .map(|guesses| (SegmentKind::Repeat, guesses));
+        match (simple, repeat) {
+            (Some(a), Some(b)) => Some(if b.1 < a.1 { b } else { a }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Fills the table `dp[j][l][b]`: the fewest log2 guesses for the first
+    /// `j` characters split into `l` segments, `b` being 1 when the last
+    /// segment is brute force. `back` records how each entry was reached.
+    fn fill_table(&self, chars: &[char]) -> (Table, Backpointers) {
+        let n = chars.len();
+        let mut dp = vec![vec![[f64::INFINITY; 2]; n + 1]; n + 1];
+        let mut back = vec![vec![[(0, 0, None); 2]; n + 1]; n + 1];
+        dp[0][0][0] = 0.0;
+        for j in 1..=n {
+            for i in 0..j {
+                let pattern = self.best_pattern(&chars[i..j]);
+                let token: String = chars[i..j].iter().collect();
+                let brute = log2(brute_force_guesses(&token));
+                for l in 0..j {
+                    for b in 0..2 {
+                        let cost = dp[i][l][b];
+                        if cost.is_infinite() {
+                            continue;
+                        }
+                        if let Some((kind, guesses)) = pattern {
+                            let total = cost + log2(guesses);
+                            if total < dp[j][l + 1][0] {
+                                dp[j][l + 1][0] = total;
+                                back[j][l + 1][0] = (i, b, Some((kind, guesses)));
+                            }
+                        }
+                        // 暴力段不能相邻
+                        if b == 0 && cost + brute < dp[j][l + 1][1] {
+                            dp[j][l + 1][1] = cost + brute;
+                            back[j][l + 1][1] = (i, b, None);
+                        }
+                    }
+                }
+            }
+        }
+        (dp, back)
+    }
+
+    pub fn decompose(&self, password: &str) -> Decomposition {
+        let chars: Vec<char> = password.chars().collect();
+        if chars.len() <= MAX_ANALYSED_LEN {
+            return self.decompose_chars(&chars);
+        }
+
+        let mut decomposition = self.decompose_chars(&chars[..MAX_ANALYSED_LEN]);
+        let mut tail: String = chars[MAX_ANALYSED_LEN..].iter().collect();
+        let segments = &mut decomposition.segments;
+        // 暴力段不能相邻，末尾已是暴力段时与剩余部分合并
+        if let Some(last) = segments.pop_if(|last| last.kind == SegmentKind::BruteForce) {
+            tail.insert_str(0, &last.token);
+        }
+        segments.push(Segment {
+            guesses: brute_force_guesses(&tail),
+            token: tail,
+            kind: SegmentKind::BruteForce,
+        });
+        let product: f64 = segments.iter().map(|s| s.guesses).product();
+        decomposition.guesses = product * factorial(segments.len());
+        decomposition
+    }
+
+    fn decompose_chars(&self, chars: &[char]) -> Decomposition {
+        let n = chars.len();
+        let (dp, back) = self.fill_table(chars);
+
+        let mut best = (f64::INFINITY, 0, 0);
+        for (l, ends) in dp[n].iter().enumerate() {
+            for (b, cost) in ends.iter().enumerate() {
+                let total = cost + log2(factorial(l));
+                if total < best.0 {
+                    best = (total, l, b);
+                }
+            }
+        }
+
+        let (total, mut l, mut b) = best;
+        let mut segments = Vec::new();
+        let mut j = n;
+        while l > 0 {
+            let (i, prev_b, pattern) = back[j][l][b];
+            let token: String = chars[i..j].iter().collect();
+            let (kind, guesses) =
+                pattern.unwrap_or_else(|| (SegmentKind::BruteForce, brute_force_guesses(&token)));
+            segments.push(Segment {
+                token,
+                kind,
+                guesses,
+            });
+            j = i;
+            l -= 1;
+            b = prev_b;
+        }
+        segments.reverse();
+        Decomposition {
+            segments,
+            guesses: if n == 0 { 0.0 } else { total.exp2() },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(decomposition: &Decomposition) -> Vec<(&str, SegmentKind)> {
+        decomposition
+            .segments
+            .iter()
+            .map(|s| (s.token.as_str(), s.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_decompose_segments() {
+        let passwords = vec!["password".to_string(), "summer".to_string()];
+        let decomposer = Decomposer::new(&passwords, Layout::Qwerty);
+
+        let result = decomposer.decompose("summer2019");
+        assert_eq!(
+            kinds(&result),
+            vec![
+                ("summer", SegmentKind::Dictionary),
+                ("2019", SegmentKind::Date)
+            ]
+        );
+        assert!((result.guesses - 2.0 * 120.0 * 2.0).abs() < 1e-6);
+
+        let result = decomposer.decompose("qwertyabcdpassword");
+        assert_eq!(
+            kinds(&result),
+            vec![
+                ("qwerty", SegmentKind::KeyboardWalk),
+                ("abcd", SegmentKind::Sequence),
+                ("password", SegmentKind::Dictionary)
+            ]
+        );
+
+        let result = decomposer.decompose("xyzxyzxyz#Kq");
+        assert_eq!(result.segments[0].kind, SegmentKind::Repeat);
+        assert_eq!(result.segments[1].kind, SegmentKind::BruteForce);
+    }
+
+    #[test]
+    fn test_brute_force_only() {
+        let decomposer = Decomposer::new(&[], Layout::Qwerty);
+        let result = decomposer.decompose("Kq#v");
+        assert_eq!(kinds(&result), vec![("Kq#v", SegmentKind::BruteForce)]);
+        assert!((result.entropy() - calculate_entropy("Kq#v") - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_long_input_is_capped() {
+        let passwords = vec!["password".to_string()];
+        let decomposer = Decomposer::new(&passwords, Layout::Qwerty);
+        let password = "Kq#v9".repeat(2000);
+        let start = std::time::Instant::now();
+        let result = decomposer.decompose(&password);
+        // 不截断时这里要几分钟
+        assert!(start.elapsed().as_secs() < 5);
+
+        let tokens: String = result.segments.iter().map(|s| s.token.as_str()).collect();
+        assert_eq!(tokens, password);
+        let last = result.segments.last().unwrap();
+        assert_eq!(last.kind, SegmentKind::BruteForce);
+        assert!(last.token.len() >= password.len() - MAX_ANALYSED_LEN);
+
+        // 分析部分以暴力段结尾时与剩余部分合并，不会出现相邻的暴力段
+        let password = format!("password{}", "Kq#v9".repeat(40));
+        let result = decomposer.decompose(&format!("{}{}", &password[..99], "x1!"));
+        assert!(result
+            .segments
+            .windows(2)
+            .all(|w| w[0].kind != SegmentKind::BruteForce || w[1].kind != SegmentKind::BruteForce));
+        assert!(
+            (result.guesses
+                - result.segments.iter().map(|s| s.guesses).product::<f64>()
+                    * factorial(result.segments.len()))
+            .abs()
+                <= result.guesses * 1e-9
+        );
+    }
+}
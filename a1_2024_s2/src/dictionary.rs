@@ -0,0 +1,276 @@
+//! The loaded password lists, in rank order, remembering which file each
+//! word came from.
+
+use crate::charset::{self, normalize};
+use crate::error::{UQEntropyError, UQEntropyResult};
+use crate::utils::log::Redacted;
+use crate::{check_password_is_valid, uqd};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+fn normalized(word: String) -> String {
+    match normalize(&word) {
+        Cow::Borrowed(_) => word,
+        Cow::Owned(nfkc) => nfkc,
+    }
+}
+
+/// A contiguous run of words loaded from one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub name: String,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    words: Vec<String>,
+    sources: Vec<Source>,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Dictionary {
+            words: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Appends the words read from file `name` after the ones already loaded,
+    /// in NFKC form.
+    pub fn add_source(&mut self, name: &str, words: Vec<String>) {
+        let words: Vec<String> = words.into_iter().map(normalized).collect();
+        self.sources.push(Source {
+            name: name.to_string(),
+            start: self.words.len(),
+            len: words.len(),
+        });
+        self.words.extend(words);
+    }
+
+    /// Appends every source of `other`, keeping their order.
+    pub fn append(&mut self, other: Dictionary) {
+        let mut words = other.words.into_iter();
+        for source in other.sources {
+            self.add_source(&source.name, words.by_ref().take(source.len).collect());
+        }
+        // 没有来源信息的词直接追加
+        self.words.extend(words);
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Name of the file the word at `index` was loaded from.
+    pub fn source_of(&self, index: usize) -> Option<&str> {
+        self.sources
+            .iter()
+            .find(|s| s.start <= index && index < s.start + s.len)
+            .map(|s| s.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Loads every file in order, text word lists with [`read_list`] and
+    /// precompiled `.uqd` files with [`uqd::load`]. Stops at the first file
+    /// that fails.
+    pub fn load(filenames: &[String]) -> UQEntropyResult<Self> {
+        log::info!(
+            "Reading {} file{}",
+            filenames.len(),
+            if filenames.len() == 1 { "" } else { "s" }
+        );
+        let mut dictionary = Dictionary::new();
+        for fname in filenames {
+            if uqd::is_uqd(fname) {
+                dictionary.append(read_uqd(fname)?);
+            } else {
+                dictionary.add_source(fname, read_list(fname)?);
+            }
+        }
+        Ok(dictionary)
+    }
+}
+
+fn read_uqd(fname: &str) -> UQEntropyResult<Dictionary> {
+    uqd::load(fname).map_err(|err| match err {
+        uqd::UqdError::Io(_) => UQEntropyError::UnreadableFile(fname.to_string()),
+        err => UQEntropyError::InvalidFile(format!(
+            "\"{}\" is not a valid dictionary ({})",
+            fname, err
+        )),
+    })
+}
+
+/// Reads the whitespace-separated passwords of a text word list.
+///
+/// The file fails if it has no password at all, or if any line has a
+/// character outside [`charset::is_allowed`] or no password, e.g. a blank
+/// line. Only the lines with invalid characters are reported.
+pub fn read_list(fname: &str) -> UQEntropyResult<Vec<String>> {
+    let file = File::open(fname).map_err(|_| UQEntropyError::UnreadableFile(fname.to_string()))?;
+    let mut passwords = Vec::new();
+    let mut invalid_lines = 0;
+    let mut rejected_lines = 0;
+    let mut total_lines = 0;
+    for line in BufReader::new(file).lines() {
+        total_lines += 1;
+        let Ok(line) = line else {
+            return Err(UQEntropyError::ReadFailed {
+                file: fname.to_string(),
+                invalid_lines,
+            });
+        };
+        let line = line.trim();
+        if let Some(c) = line.chars().find(|&c| !charset::is_allowed(c)) {
+            log::debug!(
+                "Invalid character U+{:04X} found in line: {}",
+                c as u32,
+                Redacted(line)
+            );
+            invalid_lines += 1;
+            rejected_lines += 1;
+        } else if !read_line(line, &mut passwords) {
+            rejected_lines += 1;
+        }
+    }
+    log::info!("total lines: {}", total_lines);
+    if passwords.is_empty() {
+        return Err(UQEntropyError::EmptyDictionary {
+            file: fname.to_string(),
+            invalid_lines,
+        });
+    }
+    if rejected_lines > 0 {
+        return Err(UQEntropyError::InvalidCharacters {
+            file: fname.to_string(),
+            lines: invalid_lines,
+        });
+    }
+    Ok(passwords)
+}
+
+/// Whether `word` could have been read from a text word list: a valid
+/// password with no whitespace in it.
+pub(crate) fn is_list_word(word: &str) -> bool {
+    check_password_is_valid(word) && !word.contains(char::is_whitespace)
+}
+
+/// Adds the passwords on `line`; false if it has none.
+fn read_line(line: &str, passwords: &mut Vec<String>) -> bool {
+    let mut found = false;
+    for token in line.split_whitespace() {
+        if is_list_word(token) {
+            passwords.push(token.to_string());
+            found = true;
+        } else {
+            log::info!(
+                "Filtered out token: {} from line: {}",
+                Redacted(token),
+                Redacted(line)
+            );
+        }
+    }
+    found
+}
+
+/// Words that did not come from a file have no source.
+impl From<Vec<String>> for Dictionary {
+    fn from(words: Vec<String>) -> Self {
+        Dictionary {
+            words: words.into_iter().map(normalized).collect(),
+            sources: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `content` to a fresh temporary word list.
+    fn list(name: &str, content: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("uqentropy_{}_{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_read_list() {
+        let fname = list("good", b"password 123456\n  qwerty\n");
+        let passwords = read_list(&fname).unwrap();
+        std::fs::remove_file(&fname).unwrap();
+        assert_eq!(passwords, ["password", "123456", "qwerty"]);
+    }
+
+    #[test]
+    fn test_read_list_errors() {
+        assert!(matches!(
+            read_list("no/such/file"),
+            Err(UQEntropyError::UnreadableFile(f)) if f == "no/such/file"
+        ));
+
+        let cases: [(&str, &[u8]); 4] = [
+            ("empty", b""),
+            ("control", b"pass\x01word\nabc\x02\nqwerty\n"),
+            ("blank", b"password\n\nqwerty\n"),
+            ("utf8", b"password\n\xff\n"),
+        ];
+        let errors: Vec<_> = cases
+            .iter()
+            .map(|(name, content)| {
+                let fname = list(name, content);
+                let err = read_list(&fname).unwrap_err();
+                std::fs::remove_file(&fname).unwrap();
+                err
+            })
+            .collect();
+        assert!(matches!(
+            errors[0],
+            UQEntropyError::EmptyDictionary {
+                invalid_lines: 0,
+                ..
+            }
+        ));
+        assert!(matches!(
+            errors[1],
+            UQEntropyError::InvalidCharacters { lines: 2, .. }
+        ));
+        // 空行不报告，但整个文件仍然无效
+        assert!(matches!(
+            errors[2],
+            UQEntropyError::InvalidCharacters { lines: 0, .. }
+        ));
+        assert!(errors[2]
+            .to_string()
+            .starts_with("uqentropy: invalid character found in file"));
+        assert!(matches!(errors[3], UQEntropyError::ReadFailed { .. }));
+    }
+
+    #[test]
+    fn test_load_stops_at_first_failure() {
+        let good = list("first", b"password\n");
+        let names = [good.clone(), "no/such/file".to_string(), good.clone()];
+        let err = Dictionary::load(&names).unwrap_err();
+        let loaded = Dictionary::load(std::slice::from_ref(&good)).unwrap();
+        std::fs::remove_file(&good).unwrap();
+        assert_eq!(
+            err.to_string(),
+            "uqentropy: unable to open file \"no/such/file\" for reading"
+        );
+        assert_eq!(loaded.words(), ["password"]);
+    }
+}
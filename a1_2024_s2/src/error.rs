@@ -1,11 +1,39 @@
+use crate::generate::GenerateError;
 use thiserror::Error;
 
 pub type UQEntropyResult<T, E = UQEntropyError> = Result<T, E>;
 
+/// Every way a run can fail. Each message describes the failure once; the
+/// command line decides what to print for it and maps the variants to its
+/// exit codes.
 #[derive(Debug, Error)]
 pub enum UQEntropyError {
-    #[error("usage")]
-    Usage,
-    #[error("file")]
-    File(String),
+    /// Bad command line; holds the usage line of the command.
+    #[error("{0}")]
+    Usage(&'static str),
+    #[error("uqentropy: unable to open file \"{0}\" for reading")]
+    UnreadableFile(String),
+    #[error("uqentropy: unable to open file \"{0}\" for writing")]
+    UnwritableFile(String),
+    /// Reading stopped part way through `file`, after `invalid_lines` lines
+    /// with invalid characters.
+    #[error("uqentropy: error reading file \"{file}\"")]
+    ReadFailed { file: String, invalid_lines: usize },
+    #[error("uqentropy: \"{file}\" does not contain any passwords")]
+    EmptyDictionary { file: String, invalid_lines: usize },
+    /// `lines` lines of `file` hold characters that are not allowed. The file
+    /// is also rejected, with `lines` 0, for a line without any password.
+    #[error("uqentropy: invalid character found in file \"{file}\"")]
+    InvalidCharacters { file: String, lines: usize },
+    /// A file that was read but could not be understood, e.g. a damaged
+    /// `.uqd` dictionary or a policy with a syntax error.
+    #[error("uqentropy: {0}")]
+    InvalidFile(String),
+    #[error("uqentropy: unable to generate passwords ({0})")]
+    Generate(#[from] GenerateError),
+    /// `uqentropy lint` found files the loader would reject.
+    #[error("uqentropy: {0} file(s) failed lint")]
+    LintFailed(usize),
+    #[error("No strong password(s) have been identified")]
+    NoStrong,
 }
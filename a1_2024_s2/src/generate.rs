@@ -0,0 +1,174 @@
+//! Random passwords and passphrases for `uqentropy generate`.
+//!
+//! A password of `n` symbols drawn uniformly from `k` choices has
+//! `n * log2(k)` bits even against an attacker who knows how it was made,
+//! so the generator uses the shortest length that reaches the target. Every
+//! output is then evaluated like any candidate and drawn again if the
+//! checker's own estimate falls short, e.g. when a random string happens to
+//! miss one of its character classes.
+use crate::policy::CharClass;
+use crate::{log2, Analyzer};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Draws tried for one password before giving up.
+const ATTEMPTS: usize = 1000;
+/// Joins the words of a passphrase.
+const SEPARATOR: &str = "-";
+
+#[derive(Debug, Error)]
+pub enum GenerateError {
+    #[error("at least two distinct words are needed")]
+    TooFewWords,
+    #[error("no password of {0} bits passed the checker")]
+    Unreachable(f64),
+}
+
+/// What passwords are drawn from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alphabet {
+    /// Random strings over the printable ASCII characters of some classes.
+    Chars(Vec<char>),
+    /// Diceware-style passphrases.
+    Words(Vec<String>),
+}
+
+impl Alphabet {
+    pub fn from_classes(classes: &[CharClass]) -> Self {
+        let chars = (b'!'..=b'~')
+            .map(char::from)
+            .filter(|&c| classes.iter().any(|class| class.contains(c)))
+            .collect();
+        Alphabet::Chars(chars)
+    }
+
+    /// Distinct `words`, in their original order.
+    pub fn from_words(words: &[String]) -> Self {
+        let mut seen = HashSet::new();
+        let distinct = words.iter().filter(|word| seen.insert(word.as_str()));
+        Alphabet::Words(distinct.cloned().collect())
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Alphabet::Chars(chars) => chars.len(),
+            Alphabet::Words(words) => words.len(),
+        }
+    }
+}
+
+/// A password and the entropy it was certified with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generated {
+    pub password: String,
+    pub entropy: f64,
+}
+
+pub struct Generator {
+    alphabet: Alphabet,
+    /// Characters or words per password.
+    length: usize,
+    rng: ChaCha8Rng,
+}
+
+impl Generator {
+    /// Draws passwords of at least `bits` bits; the same `seed` always
+    /// gives the same passwords, and `None` seeds from the OS.
+    pub fn new(alphabet: Alphabet, bits: f64, seed: Option<u64>) -> Result<Self, GenerateError> {
+        if alphabet.len() < 2 {
+            return Err(GenerateError::TooFewWords);
+        }
+        let length = (bits / log2(alphabet.len() as f64)).ceil().max(1.0) as usize;
+        let rng = match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        Ok(Generator {
+            alphabet,
+            length,
+            rng,
+        })
+    }
+
+    /// Entropy of the construction itself, `length * log2(alphabet)`.
+    pub fn bits(&self) -> f64 {
+        self.length as f64 * log2(self.alphabet.len() as f64)
+    }
+
+    fn draw(&mut self) -> String {
+        let rng = &mut self.rng;
+        match &self.alphabet {
+            Alphabet::Chars(chars) => (0..self.length)
+                .map(|_| *chars.choose(rng).unwrap())
+                .collect(),
+            Alphabet::Words(words) => (0..self.length)
+                .map(|_| words.choose(rng).unwrap().as_str())
+                .collect::<Vec<_>>()
+                .join(SEPARATOR),
+        }
+    }
+
+    /// Draws until `analyzer` rates a password at `target` bits or more.
+    /// The certified entropy is the lower of that rating and [`Self::bits`].
+    pub fn generate(
+        &mut self,
+        analyzer: &Analyzer,
+        target: f64,
+    ) -> Result<Generated, GenerateError> {
+        for _ in 0..ATTEMPTS {
+            let password = self.draw();
+            let entropy = analyzer.evaluate(&password).entropy.min(self.bits());
+            if entropy >= target {
+                return Ok(Generated { password, entropy });
+            }
+        }
+        Err(GenerateError::Unreachable(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Dictionary};
+
+    #[test]
+    fn test_seeded_passwords() {
+        let analyzer = Analyzer::new(Dictionary::new(), Config::new());
+        let classes = [CharClass::Lowercase, CharClass::Digit];
+        let run = |seed| {
+            let mut generator =
+                Generator::new(Alphabet::from_classes(&classes), 64.0, Some(seed)).unwrap();
+            generator.generate(&analyzer, 64.0).unwrap()
+        };
+        let first = run(7);
+        assert_eq!(first, run(7));
+        assert_ne!(first.password, run(8).password);
+        // ceil(64 / log2(36)) = 13
+        assert_eq!(first.password.len(), 13);
+        assert!(first.entropy >= 64.0);
+    }
+
+    #[test]
+    fn test_passphrases() {
+        let words: Vec<String> = ["correct", "horse", "battery", "staple", "horse"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        let alphabet = Alphabet::from_words(&words);
+        assert_eq!(alphabet.len(), 4);
+        let mut generator = Generator::new(alphabet, 20.0, Some(1)).unwrap();
+        // 4 个不同的词，每个词 2 位
+        assert_eq!(generator.bits(), 20.0);
+        let analyzer = Analyzer::new(Dictionary::from(words.clone()), Config::new());
+        let generated = generator.generate(&analyzer, 20.0).unwrap();
+        assert_eq!(generated.password.split('-').count(), 10);
+
+        assert!(matches!(
+            Generator::new(Alphabet::from_words(&words[..1]), 20.0, None),
+            Err(GenerateError::TooFewWords)
+        ));
+    }
+}
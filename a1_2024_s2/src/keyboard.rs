@@ -0,0 +1,277 @@
+//! Keyboard-walk detection.
+//!
+//! Keys are placed on a slanted grid where every row sits half a key to the
+//! right of the one above it, so each key has up to six neighbours. A password
+//! is a keyboard walk when it can be typed entirely as runs of at least
+//! [`MIN_RUN`] adjacent keys, like "qwertyuiop" or "1qaz2wsx".
+use crate::log2;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// Shortest run of adjacent keys that counts as a walk.
+pub const MIN_RUN: usize = 3;
+
+/// Offsets of the six neighbours of a key, in a fixed order so that a change
+/// of index is a change of direction.
+const NEIGHBOURS: [(i32, i32); 6] = [(-1, 0), (0, -1), (1, -1), (1, 0), (0, 1), (-1, 1)];
+
+// 每个按键写成"非shift字符+shift字符"
+const QWERTY: [&str; 4] = [
+    "`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) -_ =+",
+    "qQ wW eE rR tT yY uU iI oO pP [{ ]} \\|",
+    "aA sS dD fF gG hH jJ kK lL ;: '\"",
+    "zZ xX cC vV bB nN mM ,< .> /?",
+];
+
+const AZERTY: [&str; 4] = [
+    "² &1 é2 \"3 '4 (5 -6 è7 _8 ç9 à0 )° =+",
+    "aA zZ eE rR tT yY uU iI oO pP ^¨ $£",
+    "qQ sS dD fF gG hH jJ kK lL mM ù% *µ",
+    "<> wW xX cC vV bB nN ,? ;. :/ !§",
+];
+
+const DVORAK: [&str; 4] = [
+    "`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) [{ ]}",
+    "'\" ,< .> pP yY fF gG cC rR lL /? =+ \\|",
+    "aA oO eE uU iI dD hH tT nN sS -_",
+    ";: qQ jJ kK xX bB mM wW vV zZ",
+];
+
+/// Built-in keyboard layouts, selected with `--keyboard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl Layout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Qwerty => "qwerty",
+            Layout::Azerty => "azerty",
+            Layout::Dvorak => "dvorak",
+        }
+    }
+
+    /// Rows from the number row down, and the column of each row's first key.
+    fn rows(&self) -> ([&'static str; 4], [i32; 4]) {
+        match self {
+            Layout::Qwerty => (QWERTY, [0, 1, 1, 1]),
+            // ISO 的 "<>" 键位于 w 左侧
+            Layout::Azerty => (AZERTY, [0, 1, 1, 0]),
+            Layout::Dvorak => (DVORAK, [0, 1, 1, 1]),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Layout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Key {
+    x: i32,
+    y: i32,
+    shifted: bool,
+}
+
+/// A password that was typed entirely as keyboard walks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardWalk {
+    pub layout: Layout,
+    /// Number of separate runs, e.g. 2 for "1qaz2wsx".
+    pub runs: usize,
+    /// Direction changes summed over all runs, counting each run's first
+    /// direction as a turn.
+    pub turns: usize,
+    /// Characters typed with shift held.
+    pub shifted: usize,
+    /// Estimated number of walks a cracker tries before this one.
+    pub guesses: f64,
+}
+
+impl KeyboardWalk {
+    /// Entropy in the same terms as a dictionary match on guess `guesses`.
+    pub fn entropy(&self) -> f64 {
+        log2(2.0 * self.guesses)
+    }
+}
+
+/// Adjacency graph of one layout.
+pub struct Keyboard {
+    layout: Layout,
+    keys: HashMap<char, Key>,
+    starts: f64,
+    average_degree: f64,
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+impl Keyboard {
+    pub fn new(layout: Layout) -> Self {
+        let (rows, offsets) = layout.rows();
+        let mut keys = HashMap::new();
+        let mut positions = HashSet::new();
+        for (y, (row, offset)) in rows.iter().zip(offsets).enumerate() {
+            for (i, token) in row.split(' ').enumerate() {
+                let (x, y) = (offset + i as i32, y as i32);
+                positions.insert((x, y));
+                for (n, c) in token.chars().enumerate() {
+                    keys.entry(c).or_insert(Key {
+                        x,
+                        y,
+                        shifted: n == 1,
+                    });
+                }
+            }
+        }
+        let degree: usize = positions
+            .iter()
+            .map(|(x, y)| {
+                NEIGHBOURS
+                    .iter()
+                    .filter(|(dx, dy)| positions.contains(&(x + dx, y + dy)))
+                    .count()
+            })
+            .sum();
+        Keyboard {
+            layout,
+            keys,
+            starts: positions.len() as f64,
+            average_degree: degree as f64 / positions.len() as f64,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Direction from `a` to `b` if the two keys are neighbours.
+    fn direction(&self, a: char, b: char) -> Option<usize> {
+        let (a, b) = (self.keys.get(&a)?, self.keys.get(&b)?);
+        NEIGHBOURS
+            .iter()
+            .position(|(dx, dy)| a.x + dx == b.x && a.y + dy == b.y)
+    }
+
+    /// Walks of up to `length` keys with at most `turns` turns, following
+    /// zxcvbn's spatial estimate.
+    fn run_guesses(&self, length: usize, turns: usize) -> f64 {
+        let mut guesses = 0.0;
+        for i in 2..=length {
+            for j in 1..=turns.min(i - 1) {
+                guesses +=
+                    n_choose_k(i - 1, j - 1) * self.starts * self.average_degree.powi(j as i32);
+            }
+        }
+        guesses
+    }
+
+    /// Splits `password` into runs of adjacent keys; `None` unless every run
+    /// is at least [`MIN_RUN`] keys long.
+    pub fn find_walk(&self, password: &str) -> Option<KeyboardWalk> {
+        let chars: Vec<char> = password.chars().collect();
+        let mut walk = KeyboardWalk {
+            layout: self.layout,
+            runs: 0,
+            turns: 0,
+            shifted: 0,
+            guesses: 1.0,
+        };
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = start + 1;
+            let mut turns = 0;
+            let mut last_direction = None;
+            while end < chars.len() {
+                let Some(direction) = self.direction(chars[end - 1], chars[end]) else {
+                    break;
+                };
+                if last_direction != Some(direction) {
+                    turns += 1;
+                    last_direction = Some(direction);
+                }
+                end += 1;
+            }
+            if end - start < MIN_RUN {
+                return None;
+            }
+            walk.runs += 1;
+            walk.turns += turns;
+            walk.guesses *= self.run_guesses(end - start, turns);
+            start = end;
+        }
+        if walk.runs == 0 {
+            return None;
+        }
+
+        walk.shifted = chars.iter().filter(|c| self.keys[c].shifted).count();
+        let unshifted = chars.len() - walk.shifted;
+        if unshifted == 0 {
+            walk.guesses *= 2.0;
+        } else if walk.shifted > 0 {
+            let variants: f64 = (1..=walk.shifted.min(unshifted))
+                .map(|i| n_choose_k(chars.len(), i))
+                .sum();
+            walk.guesses *= variants;
+        }
+        Some(walk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qwerty_walks() {
+        let keyboard = Keyboard::new(Layout::Qwerty);
+        let walk = keyboard.find_walk("qwertyuiop").unwrap();
+        assert_eq!((walk.runs, walk.turns, walk.shifted), (1, 1, 0));
+        assert!(walk.entropy() < 35.0);
+
+        let walk = keyboard.find_walk("1qaz2wsx").unwrap();
+        assert_eq!((walk.runs, walk.turns), (2, 2));
+        assert!(walk.entropy() < 35.0);
+
+        assert_eq!(keyboard.find_walk("zaq1").unwrap().runs, 1);
+        assert!(keyboard.find_walk("QWERTY").unwrap().shifted == 6);
+        assert!(keyboard.find_walk("password").is_none());
+        assert!(keyboard.find_walk("qwe1").is_none());
+    }
+
+    #[test]
+    fn test_other_layouts() {
+        assert!(Keyboard::new(Layout::Azerty)
+            .find_walk("azertyuiop")
+            .is_some());
+        assert!(Keyboard::new(Layout::Azerty).find_walk("qwerty").is_none());
+        assert!(Keyboard::new(Layout::Dvorak)
+            .find_walk("aoeuidhtns")
+            .is_some());
+        assert_eq!("dvorak".parse::<Layout>(), Ok(Layout::Dvorak));
+        assert!("colemak".parse::<Layout>().is_err());
+    }
+}
@@ -1,22 +1,85 @@
+pub mod analyzer;
+pub mod breach;
+pub mod charset;
+pub mod cli;
+pub mod decompose;
+pub mod dictionary;
 pub mod error;
+pub mod generate;
+pub mod keyboard;
+pub mod lint;
+pub mod markov;
+pub mod output;
+pub mod policy;
+pub mod repl;
+pub mod strategy;
+pub mod uqd;
 pub mod utils;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+pub use analyzer::{Analyzer, Evaluation, MatchReport, TraceStep};
+pub use breach::BreachDb;
+pub use decompose::{Decomposition, Segment, SegmentKind};
+pub use dictionary::Dictionary;
+pub use keyboard::{KeyboardWalk, Layout};
+pub use markov::{MarkovEstimate, MarkovModel};
+pub use output::{OutputFormat, Record, RecordWriter};
+pub use policy::{Policy, Rating, Violation};
+pub use strategy::{GuessStrategy, Hit, StrategyRegistry};
+
 // Export core functions from uqentropy for testing
 pub struct Config {
     pub leet: bool,
+    /// Substitutions from `--leet-table`, the built-in table otherwise.
+    pub leet_table: Option<strategy::LeetTable>,
     pub case_sensitive: bool,
     pub digit_append: bool,
     pub double_check: bool,
+    pub dates: bool,
     pub num_digits: usize,
+    pub keyboard: Option<keyboard::Layout>,
+    pub decompose: bool,
+    /// Also try case, leet and digit-append chained together.
+    pub combine: bool,
+    pub format: output::OutputFormat,
+    /// Append log records to this file; nothing is logged by default.
+    pub log_file: Option<String>,
+    /// Most verbose level logged, from `--log-level`.
+    pub log_level: Option<log::LevelFilter>,
+    /// Read candidates from this file instead of stdin.
+    pub candidates: Option<String>,
+    /// Worker threads used to evaluate candidates.
+    pub jobs: usize,
+    /// Character n-gram model from `--markov`.
+    pub markov: Option<markov::MarkovModel>,
+    /// Breached-password corpus from `--breach-db`.
+    pub breach_db: Option<breach::BreachDb>,
+    /// Policy from `--policy`; [`Policy::DEFAULT`] applies without one.
+    pub policy: Option<policy::Policy>,
 }
 
 impl Config {
     pub fn new() -> Self {
         Config {
             leet: false,
+            leet_table: None,
             case_sensitive: false,
             digit_append: false,
             double_check: false,
+            dates: false,
             num_digits: 0,
+            keyboard: None,
+            decompose: false,
+            combine: false,
+            format: output::OutputFormat::Text,
+            log_file: None,
+            log_level: None,
+            candidates: None,
+            jobs: 1,
+            markov: None,
+            breach_db: None,
+            policy: None,
         }
     }
 }
@@ -27,71 +90,42 @@ impl Default for Config {
 }
 
 // 添加自定义log2函数，与原始代码保持一致
-fn log2(x: f64) -> f64 {
+pub(crate) fn log2(x: f64) -> f64 {
     x.log2()
 }
 
+/// `length * log2(pool)`, the length counted in grapheme clusters and the
+/// pool being the sum of the [`charset::Charset`] sizes present.
 pub fn calculate_entropy(password: &str) -> f64 {
-    let mut has_lower = false;
-    let mut has_upper = false;
-    let mut has_digit = false;
-    let mut has_symbol = false;
-
-    for c in password.chars() {
-        if c.is_ascii_lowercase() {
-            has_lower = true;
-        } else if c.is_ascii_uppercase() {
-            has_upper = true;
-        } else if c.is_ascii_digit() {
-            has_digit = true;
-        } else {
-            has_symbol = true;
+    let mut classes = Vec::new();
+    for grapheme in password.graphemes(true) {
+        let class = charset::Charset::of(grapheme);
+        if !classes.contains(&class) {
+            classes.push(class);
         }
     }
-
-    let mut s = 0;
-    if has_digit {
-        s += 10;
-    }
-    if has_lower {
-        s += 26;
-    }
-    if has_upper {
-        s += 26;
-    }
-    if has_symbol {
-        s += 32;
-    }
+    let s: u32 = classes.iter().map(|class| class.size()).sum();
 
     // 处理s为0的情况，避免log2(0)产生NaN
     if s == 0 {
         return 0.0;
     }
 
-    let result = password.len() as f64 * log2(s as f64);
+    let result = charset::grapheme_len(password) as f64 * log2(s as f64);
     floor_to_one_decimal(result)
 }
 
+/// Rating under the default 35/60/120 bit bands.
 pub fn map_to_strength(entropy: f64) -> &'static str {
-    if entropy < 35.0 {
-        "very weak"
-    } else if entropy < 60.0 {
-        "weak"
-    } else if entropy < 120.0 {
-        "strong"
-    } else {
-        "very strong"
-    }
+    policy::Policy::DEFAULT.rate(entropy).as_str()
 }
 
 pub fn check_password_is_valid(password: &str) -> bool {
     if password.is_empty() {
         return false;
     }
-    // 只允许可打印的ASCII字符，不包括控制字符
-    password
-        .chars()
-        .all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
+    // 不允许控制字符
+    password.chars().all(charset::is_allowed)
 }
 
 pub fn floor_to_one_decimal(x: f64) -> f64 {
@@ -101,7 +135,8 @@ pub fn floor_to_one_decimal(x: f64) -> f64 {
 pub fn get_letter_count(s: &str) -> i32 {
     let mut count = 0;
     for c in s.chars() {
-        if c.is_ascii_alphabetic() {
+        // 只统计有大小写之分的字母
+        if c.is_lowercase() || c.is_uppercase() {
             count += 1;
         }
     }
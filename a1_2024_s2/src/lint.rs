@@ -0,0 +1,191 @@
+//! Checks on word lists before they are deployed, for `uqentropy lint`.
+//!
+//! [`crate::dictionary::read_list`] stops at the first bad file and only says
+//! which file it was; [`FileLint::read`] goes through the whole file and
+//! records where every problem is. A [`Problem`] would make the loader
+//! reject the file. Duplicates, very weak tokens and the overlap between
+//! files are only counted.
+use crate::calculate_entropy;
+use crate::charset::{self, normalize};
+use crate::error::{UQEntropyError, UQEntropyResult};
+use crate::policy::{Policy, Rating};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Something that makes the loader reject a file. Lines and columns start
+/// at 1; columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    InvalidChar {
+        line: usize,
+        column: usize,
+        ch: char,
+    },
+    NotUtf8 {
+        line: usize,
+    },
+    /// A line without any password, e.g. a blank one.
+    NoPasswords {
+        line: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::InvalidChar { line, column, ch } => write!(
+                f,
+                "line {}, column {}: invalid character U+{:04X}",
+                line, column, *ch as u32
+            ),
+            Problem::NotUtf8 { line } => write!(f, "line {}: not valid UTF-8", line),
+            Problem::NoPasswords { line } => write!(f, "line {}: no passwords", line),
+        }
+    }
+}
+
+/// What was found in one word list.
+#[derive(Debug, Clone, Default)]
+pub struct FileLint {
+    pub file: String,
+    pub tokens: usize,
+    /// Tokens equal to an earlier token of the same file once normalised.
+    pub duplicates: usize,
+    /// Tokens rated very weak under the default bands.
+    pub very_weak: usize,
+    pub problems: Vec<Problem>,
+    distinct: HashSet<String>,
+}
+
+impl FileLint {
+    /// Fails only if `fname` cannot be read at all.
+    pub fn read(fname: &str) -> UQEntropyResult<Self> {
+        let file =
+            File::open(fname).map_err(|_| UQEntropyError::UnreadableFile(fname.to_string()))?;
+        let mut lint = FileLint {
+            file: fname.to_string(),
+            ..FileLint::default()
+        };
+        for (i, line) in BufReader::new(file).split(b'\n').enumerate() {
+            let line = line.map_err(|_| UQEntropyError::ReadFailed {
+                file: fname.to_string(),
+                invalid_lines: 0,
+            })?;
+            lint.check_line(i + 1, &line);
+        }
+        Ok(lint)
+    }
+
+    fn check_line(&mut self, number: usize, bytes: &[u8]) {
+        let Ok(raw) = std::str::from_utf8(bytes) else {
+            self.problems.push(Problem::NotUtf8 { line: number });
+            return;
+        };
+        // 与 read_list 一样先去掉首尾空白，列号仍然按原始行计算
+        let line = raw.trim();
+        let indent = raw.trim_start().len();
+        let indent = raw[..raw.len() - indent].chars().count();
+        let invalid = line
+            .chars()
+            .enumerate()
+            .filter(|&(_, ch)| !charset::is_allowed(ch))
+            .map(|(i, ch)| Problem::InvalidChar {
+                line: number,
+                column: indent + i + 1,
+                ch,
+            });
+        let before = self.problems.len();
+        self.problems.extend(invalid);
+        if self.problems.len() > before {
+            return;
+        }
+        if line.is_empty() {
+            self.problems.push(Problem::NoPasswords { line: number });
+        }
+        for token in line.split_whitespace() {
+            self.add_token(token);
+        }
+    }
+
+    fn add_token(&mut self, token: &str) {
+        self.tokens += 1;
+        if !self.distinct.insert(normalize(token).into_owned()) {
+            self.duplicates += 1;
+        }
+        if Policy::DEFAULT.rate(calculate_entropy(token)) == Rating::VeryWeak {
+            self.very_weak += 1;
+        }
+    }
+
+    /// Whether the loader would reject the file.
+    pub fn has_errors(&self) -> bool {
+        self.tokens == 0 || !self.problems.is_empty()
+    }
+
+    /// Distinct tokens found in both files.
+    pub fn overlap(&self, other: &FileLint) -> usize {
+        self.distinct.intersection(&other.distinct).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(lines: &[&[u8]]) -> FileLint {
+        let mut lint = FileLint::default();
+        for (i, line) in lines.iter().enumerate() {
+            lint.check_line(i + 1, line);
+        }
+        lint
+    }
+
+    #[test]
+    fn test_counts() {
+        let found = lint(&[b"letmein Password123!", b" letmein\r", b"abc"]);
+        assert_eq!(found.tokens, 4);
+        assert_eq!(found.duplicates, 1);
+        // "letmein" 两次和 "abc"
+        assert_eq!(found.very_weak, 3);
+        assert!(!found.has_errors());
+    }
+
+    #[test]
+    fn test_problems() {
+        let found = lint(&[b"ok", b"  pa\x01ss\x02", b"", b"\xff\xfe", b"fine"]);
+        assert_eq!(
+            found.problems,
+            [
+                Problem::InvalidChar {
+                    line: 2,
+                    column: 5,
+                    ch: '\x01'
+                },
+                Problem::InvalidChar {
+                    line: 2,
+                    column: 8,
+                    ch: '\x02'
+                },
+                Problem::NoPasswords { line: 3 },
+                Problem::NotUtf8 { line: 4 },
+            ]
+        );
+        assert_eq!(found.tokens, 2);
+        assert!(found.has_errors());
+        assert_eq!(
+            found.problems[0].to_string(),
+            "line 2, column 5: invalid character U+0001"
+        );
+        assert!(lint(&[]).has_errors());
+    }
+
+    #[test]
+    fn test_overlap() {
+        let first = lint(&[b"password qwerty letmein"]);
+        let second = lint(&[b"qwerty dragon password qwerty"]);
+        assert_eq!(first.overlap(&second), 2);
+        assert_eq!(second.overlap(&first), 2);
+    }
+}
@@ -0,0 +1,287 @@
+//! Character n-gram model written by `uqentropy train` and used by
+//! `--markov`.
+//!
+//! The model counts, for every context of the previous `ORDER - 1`
+//! characters, which character came next in the training words, with start
+//! and end markers around each word. A candidate's probability is the
+//! product of its transitions with add-one smoothing, and a cracker trying
+//! guesses in order of probability needs about `1 / p` of them to reach it.
+//!
+//! All integers in the file are little-endian:
+//!
+//! ```text
+//! magic "UQM\0" | version u16 | order u16 | context count u32
+//! per context: length u32, bytes, entry count u32
+//!     per entry: character u32, count u32
+//! CRC-32 of everything above, u32
+//! ```
+use crate::log2;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use thiserror::Error;
+
+/// Characters per n-gram, the last one being predicted.
+pub const ORDER: usize = 3;
+
+const MAGIC: &[u8; 4] = b"UQM\0";
+const VERSION: u16 = 1;
+/// Pads the context before the first character; control characters never
+/// appear in a valid word.
+const START: char = '\u{2}';
+const END: char = '\u{3}';
+/// Outcomes always assumed when smoothing: printable ASCII plus the end
+/// marker. Any other character in the training words or the candidate adds
+/// one more.
+const BASE_ALPHABET: u64 = 96;
+
+fn in_base_alphabet(c: char) -> bool {
+    c == END || (' '..='~').contains(&c)
+}
+
+#[derive(Debug, Error)]
+pub enum MarkovError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("not a model file")]
+    BadMagic,
+    #[error("unsupported model version {0}")]
+    Version(u16),
+    #[error("checksum mismatch")]
+    Checksum,
+    #[error("truncated file")]
+    Truncated,
+    #[error("invalid character in model")]
+    Invalid,
+}
+
+/// Estimate for one candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkovEstimate {
+    /// Guesses a cracker following the model needs, at least 1.
+    pub guesses: f64,
+}
+
+impl MarkovEstimate {
+    /// Entropy in the same terms as a dictionary match on guess `guesses`.
+    pub fn entropy(&self) -> f64 {
+        log2(2.0 * self.guesses)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Context {
+    total: u64,
+    next: BTreeMap<char, u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkovModel {
+    order: usize,
+    contexts: BTreeMap<String, Context>,
+    /// Characters outside the base alphabet that were seen in training;
+    /// not stored, as every character is the next one of some context.
+    extra: BTreeSet<char>,
+}
+
+/// `word` between its markers, as the sequence the model walks over.
+fn padded(word: &str, order: usize) -> Vec<char> {
+    let mut chars = vec![START; order - 1];
+    chars.extend(word.chars());
+    chars.push(END);
+    chars
+}
+
+impl MarkovModel {
+    /// Counts the n-grams of every word, each occurrence once.
+    pub fn train(words: &[String]) -> Self {
+        let mut model = MarkovModel {
+            order: ORDER,
+            ..MarkovModel::default()
+        };
+        for word in words {
+            for gram in padded(word, ORDER).windows(ORDER) {
+                let (context, next) = gram.split_at(ORDER - 1);
+                let entry = model.contexts.entry(context.iter().collect()).or_default();
+                entry.total += 1;
+                *entry.next.entry(next[0]).or_default() += 1;
+            }
+        }
+        model.find_extra();
+        model
+    }
+
+    fn find_extra(&mut self) {
+        self.extra = self
+            .contexts
+            .values()
+            .flat_map(|entry| entry.next.keys().copied())
+            .filter(|&c| !in_base_alphabet(c))
+            .collect();
+    }
+
+    /// Probability-ordered guesses needed to reach `password`.
+    pub fn estimate(&self, password: &str) -> MarkovEstimate {
+        let unseen: BTreeSet<char> = password
+            .chars()
+            .filter(|&c| !in_base_alphabet(c) && !self.extra.contains(&c))
+            .collect();
+        let alphabet = BASE_ALPHABET + (self.extra.len() + unseen.len()) as u64;
+        let mut bits = 0.0;
+        for gram in padded(password, self.order).windows(self.order) {
+            let (context, next) = gram.split_at(self.order - 1);
+            let context = self.contexts.get(&context.iter().collect::<String>());
+            let (seen, total) = context.map_or((0, 0), |c| {
+                (c.next.get(&next[0]).copied().unwrap_or(0), c.total)
+            });
+            bits -= log2((seen as f64 + 1.0) / ((total + alphabet) as f64));
+        }
+        MarkovEstimate {
+            guesses: bits.exp2().max(1.0),
+        }
+    }
+
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        buf.extend_from_slice(&(self.order as u16).to_le_bytes());
+        buf.extend_from_slice(&(self.contexts.len() as u32).to_le_bytes());
+        for (context, entry) in &self.contexts {
+            buf.extend_from_slice(&(context.len() as u32).to_le_bytes());
+            buf.extend_from_slice(context.as_bytes());
+            buf.extend_from_slice(&(entry.next.len() as u32).to_le_bytes());
+            for (&c, &count) in &entry.next {
+                buf.extend_from_slice(&(c as u32).to_le_bytes());
+                buf.extend_from_slice(&count.to_le_bytes());
+            }
+        }
+        let checksum = crc32fast::hash(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+        out.write_all(&buf)?;
+        out.flush()
+    }
+
+    /// Parses a whole model file.
+    pub fn parse(data: &[u8]) -> Result<Self, MarkovError> {
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(MarkovError::BadMagic);
+        }
+        let (body, checksum) = data.split_at(data.len().saturating_sub(4).max(MAGIC.len()));
+        let mut reader = Reader {
+            data: &body[MAGIC.len()..],
+        };
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(MarkovError::Version(version));
+        }
+        if checksum.len() != 4
+            || crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().unwrap())
+        {
+            return Err(MarkovError::Checksum);
+        }
+        let order = reader.u16()? as usize;
+        if order < 2 {
+            return Err(MarkovError::Invalid);
+        }
+        let mut model = MarkovModel {
+            order,
+            ..MarkovModel::default()
+        };
+        for _ in 0..reader.u32()? {
+            let len = reader.u32()? as usize;
+            let context = std::str::from_utf8(reader.bytes(len)?)
+                .map_err(|_| MarkovError::Invalid)?
+                .to_string();
+            let mut entry = Context::default();
+            for _ in 0..reader.u32()? {
+                let c = char::from_u32(reader.u32()?).ok_or(MarkovError::Invalid)?;
+                let count = reader.u32()?;
+                entry.total += u64::from(count);
+                entry.next.insert(c, count);
+            }
+            model.contexts.insert(context, entry);
+        }
+        model.find_extra();
+        Ok(model)
+    }
+
+    pub fn load(path: &str) -> Result<Self, MarkovError> {
+        Self::parse(&fs::read(path)?)
+    }
+}
+
+/// Reads the fields of a model image in order.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MarkovError> {
+        if self.data.len() < len {
+            return Err(MarkovError::Truncated);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, MarkovError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, MarkovError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_estimates_follow_training() {
+        let model = MarkovModel::train(&words(&["password", "passport", "pass123"]));
+        let likely = model.estimate("passwort");
+        let unlikely = model.estimate("zqxjvkwy");
+        assert!(likely.guesses < unlikely.guesses);
+        // 开头的上下文出现过 3 次，之后的上下文都未见过，概率为 1/96
+        let bits = 99_f64.log2() + 8.0 * 96_f64.log2();
+        assert!((unlikely.entropy() - (1.0 + bits)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_non_ascii_alphabet() {
+        let model = MarkovModel::train(&words(&["密码", "password"]));
+        // 平滑的字母表是 96 个基本字符，加上训练中的 密、码 和候选中的 我、们
+        let estimate = model.estimate("我们");
+        let bits = 102_f64.log2() + 2.0 * 100_f64.log2();
+        assert!((estimate.entropy() - (1.0 + bits)).abs() < 1e-9);
+        assert!(model.estimate("密码").guesses < estimate.guesses);
+        // ASCII 候选只多算训练中的两个字符
+        let bits = 100_f64.log2() + 3.0 * 98_f64.log2();
+        assert!((model.estimate("zzz").entropy() - (1.0 + bits)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let model = MarkovModel::train(&words(&["hello", "help", "密码"]));
+        let mut image = Vec::new();
+        model.write(&mut image).unwrap();
+        assert_eq!(MarkovModel::parse(&image).unwrap(), model);
+
+        image[12] ^= 1;
+        assert!(matches!(
+            MarkovModel::parse(&image),
+            Err(MarkovError::Checksum)
+        ));
+        assert!(matches!(
+            MarkovModel::parse(b"UQD\0"),
+            Err(MarkovError::BadMagic)
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! Machine-readable output for `--format json|csv`.
+//!
+//! JSON output is one object per line so it can be streamed; CSV output
+//! starts with a header row. Missing values are `null` in JSON and empty
+//! fields in CSV.
+use crate::policy::{Rating, Violation};
+use crate::{floor_to_one_decimal, map_to_strength, KeyboardWalk, MatchReport};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The result for one candidate password.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    pub password: String,
+    pub entropy: f64,
+    pub rating: &'static str,
+    pub strategy: Option<String>,
+    pub guess_number: Option<u64>,
+    pub source: Option<String>,
+    /// Layout of the keyboard walk, only with `--keyboard`; empty when the
+    /// password is not a walk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Option<&'static str>>,
+    /// Direction changes in the keyboard walk, only with `--keyboard`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turns: Option<Option<usize>>,
+    /// Guesses estimated by the model, only with `--markov`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markov_guesses: Option<f64>,
+    /// Times the password appears in the corpus, only with `--breach-db`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breach_count: Option<u64>,
+    /// Broken policy rules separated by "; ", only with `--policy`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<String>,
+}
+
+impl Record {
+    /// `report` is the dictionary match, if a dictionary was searched.
+    pub fn new(password: &str, entropy: f64, report: Option<&MatchReport>) -> Self {
+        let report = report.filter(|r| r.is_match());
+        Record {
+            password: password.to_string(),
+            entropy: floor_to_one_decimal(entropy),
+            rating: map_to_strength(entropy),
+            strategy: report.and_then(|r| r.strategy.clone()),
+            guess_number: report.map(|r| r.guess_number),
+            source: report.and_then(|r| r.source.clone()),
+            layout: None,
+            turns: None,
+            markov_guesses: None,
+            breach_count: None,
+            violations: None,
+        }
+    }
+
+    /// Replaces the default rating, e.g. with one from `--policy`.
+    pub fn with_rating(mut self, rating: Rating) -> Self {
+        self.rating = rating.as_str();
+        self
+    }
+
+    /// Adds the `--keyboard` columns. A walk that takes fewer guesses than
+    /// the dictionary match is reported as the `keyboard-walk` strategy
+    /// instead.
+    pub fn with_walk(mut self, walk: Option<&KeyboardWalk>) -> Self {
+        self.layout = Some(walk.map(|w| w.layout.name()));
+        self.turns = Some(walk.map(|w| w.turns));
+        if let Some(walk) = walk {
+            if self.guess_number.is_none_or(|g| walk.guesses < g as f64) {
+                self.strategy = Some("keyboard-walk".to_string());
+                self.guess_number = Some(walk.guesses.round() as u64);
+                self.source = None;
+            }
+        }
+        self
+    }
+
+    pub fn with_markov_guesses(mut self, guesses: f64) -> Self {
+        self.markov_guesses = Some(guesses.round());
+        self
+    }
+
+    pub fn with_breach_count(mut self, count: u64) -> Self {
+        self.breach_count = Some(count);
+        self
+    }
+
+    /// Lists the `--policy` rules the candidate broke.
+    pub fn with_violations(mut self, violations: &[Violation]) -> Self {
+        let violations: Vec<_> = violations.iter().map(|v| v.to_string()).collect();
+        self.violations = Some(violations.join("; "));
+        self
+    }
+}
+
+pub enum RecordWriter<W: Write> {
+    Json(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// `None` for [`OutputFormat::Text`], which is printed line by line.
+    pub fn new(format: OutputFormat, out: W) -> Option<Self> {
+        match format {
+            OutputFormat::Text => None,
+            OutputFormat::Json => Some(RecordWriter::Json(out)),
+            OutputFormat::Csv => Some(RecordWriter::Csv(Box::new(csv::Writer::from_writer(out)))),
+        }
+    }
+
+    /// Writes and flushes one record.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self {
+            RecordWriter::Json(out) => {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+                out.flush()
+            }
+            RecordWriter::Csv(out) => {
+                out.serialize(record)?;
+                out.flush()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        Record {
+            password: "pass,word\"1".to_string(),
+            entropy: 12.5,
+            rating: "very weak",
+            strategy: Some("basic".to_string()),
+            guess_number: Some(3),
+            source: None,
+            layout: None,
+            turns: None,
+            markov_guesses: None,
+            breach_count: None,
+            violations: None,
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let mut writer = RecordWriter::new(OutputFormat::Json, Vec::new()).unwrap();
+        writer.write(&record()).unwrap();
+        let RecordWriter::Json(out) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"password\":\"pass,word\\\"1\",\"entropy\":12.5,\"rating\":\"very weak\",\
+             \"strategy\":\"basic\",\"guess_number\":3,\"source\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_header_and_quoting() {
+        let mut writer = RecordWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        writer.write(&record()).unwrap();
+        let RecordWriter::Csv(out) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            String::from_utf8(out.into_inner().unwrap()).unwrap(),
+            "password,entropy,rating,strategy,guess_number,source\n\
+             \"pass,word\"\"1\",12.5,very weak,basic,3,\n"
+        );
+    }
+
+    #[test]
+    fn test_policy_column() {
+        let violations = [
+            Violation::MissingClass(crate::policy::CharClass::Digit),
+            Violation::TooShort {
+                length: 4,
+                minimum: 8,
+            },
+        ];
+        let record = Record::new("Pass", 22.8, None)
+            .with_rating(Rating::Weak)
+            .with_violations(&violations);
+        let mut writer = RecordWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        writer.write(&record).unwrap();
+        let RecordWriter::Csv(out) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            String::from_utf8(out.into_inner().unwrap()).unwrap(),
+            "password,entropy,rating,strategy,guess_number,source,violations\n\
+             Pass,22.8,weak,,,,no digit character; \
+             4 characters long but at least 8 are required\n"
+        );
+    }
+
+    #[test]
+    fn test_walk_columns() {
+        let walk = KeyboardWalk {
+            layout: crate::Layout::Qwerty,
+            runs: 1,
+            turns: 2,
+            shifted: 0,
+            guesses: 2.0,
+        };
+        let record = Record::new("qwe", 2.0, None).with_walk(Some(&walk));
+        let mut writer = RecordWriter::new(OutputFormat::Json, Vec::new()).unwrap();
+        writer.write(&record).unwrap();
+        let RecordWriter::Json(out) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"password\":\"qwe\",\"entropy\":2.0,\"rating\":\"very weak\",\
+             \"strategy\":\"keyboard-walk\",\"guess_number\":2,\"source\":null,\
+             \"layout\":\"qwerty\",\"turns\":2}\n"
+        );
+
+        // 不是键盘路径的行留空；字典匹配猜测更少时保留字典匹配
+        let mut writer = RecordWriter::new(OutputFormat::Csv, Vec::new()).unwrap();
+        writer
+            .write(&Record::new("pass", 3.0, None).with_walk(None))
+            .unwrap();
+        let mut record = Record::new("qwe", 2.0, None);
+        record.strategy = Some("basic".to_string());
+        record.guess_number = Some(1);
+        writer.write(&record.with_walk(Some(&walk))).unwrap();
+        let RecordWriter::Csv(out) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            String::from_utf8(out.into_inner().unwrap()).unwrap(),
+            "password,entropy,rating,strategy,guess_number,source,layout,turns\n\
+             pass,3.0,very weak,,,,,\n\
+             qwe,2.0,very weak,basic,1,,qwerty,2\n"
+        );
+    }
+}
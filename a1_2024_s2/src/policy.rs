@@ -0,0 +1,281 @@
+//! Password policy loaded with `--policy FILE`.
+//!
+//! A policy is a TOML file; every key is optional and defaults to the
+//! built-in behaviour:
+//!
+//! ```toml
+//! min_rating = "strong"
+//! min_length = 12
+//! require = ["lowercase", "uppercase", "digit", "symbol"]
+//!
+//! [bands]
+//! weak = 35.0
+//! strong = 60.0
+//! very_strong = 120.0
+//! ```
+//!
+//! A candidate is accepted when it breaks none of the rules; the exit status
+//! is 14 when no candidate was accepted.
+use crate::charset::grapheme_len;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Strength ratings from weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum Rating {
+    #[serde(rename = "very weak")]
+    VeryWeak,
+    #[serde(rename = "weak")]
+    Weak,
+    #[serde(rename = "strong")]
+    Strong,
+    #[serde(rename = "very strong")]
+    VeryStrong,
+}
+
+impl Rating {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rating::VeryWeak => "very weak",
+            Rating::Weak => "weak",
+            Rating::Strong => "strong",
+            Rating::VeryStrong => "very strong",
+        }
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lowest entropy, in bits, of each rating above "very weak".
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bands {
+    pub weak: f64,
+    pub strong: f64,
+    pub very_strong: f64,
+}
+
+impl Default for Bands {
+    fn default() -> Self {
+        Policy::DEFAULT.bands
+    }
+}
+
+/// Character classes a policy can require.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharClass {
+    /// Letters of any cased script count as lower or upper case.
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharClass::Lowercase => c.is_lowercase(),
+            CharClass::Uppercase => c.is_uppercase(),
+            CharClass::Digit => c.is_numeric(),
+            CharClass::Symbol => !c.is_alphanumeric(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CharClass::Lowercase => "lowercase",
+            CharClass::Uppercase => "uppercase",
+            CharClass::Digit => "digit",
+            CharClass::Symbol => "symbol",
+        }
+    }
+}
+
+impl FromStr for CharClass {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowercase" => Ok(CharClass::Lowercase),
+            "uppercase" => Ok(CharClass::Uppercase),
+            "digit" => Ok(CharClass::Digit),
+            "symbol" => Ok(CharClass::Symbol),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A rule a candidate broke.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    Rating { rating: Rating, minimum: Rating },
+    MissingClass(CharClass),
+    TooShort { length: usize, minimum: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Rating { rating, minimum } => {
+                write!(f, "rated {} but at least {} is required", rating, minimum)
+            }
+            Violation::MissingClass(class) => write!(f, "no {} character", class.name()),
+            Violation::TooShort { length, minimum } => {
+                write!(
+                    f,
+                    "{} characters long but at least {} are required",
+                    length, minimum
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("unable to read policy file")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("rating bands must be increasing")]
+    Bands,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub bands: Bands,
+    /// Lowest rating an accepted candidate may have.
+    pub min_rating: Rating,
+    pub require: Vec<CharClass>,
+    pub min_length: usize,
+}
+
+impl Policy {
+    /// The behaviour without `--policy`: the fixed 35/60/120 bands, and
+    /// anything rated "strong" or better is accepted.
+    pub const DEFAULT: Policy = Policy {
+        bands: Bands {
+            weak: 35.0,
+            strong: 60.0,
+            very_strong: 120.0,
+        },
+        min_rating: Rating::Strong,
+        require: Vec::new(),
+        min_length: 0,
+    };
+
+    /// Parses a policy from TOML text.
+    pub fn parse(text: &str) -> Result<Self, PolicyError> {
+        let policy: Policy = toml::from_str(text)?;
+        let bands = &policy.bands;
+        if !(bands.weak <= bands.strong && bands.strong <= bands.very_strong) {
+            return Err(PolicyError::Bands);
+        }
+        Ok(policy)
+    }
+
+    pub fn load(path: &str) -> Result<Self, PolicyError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn rate(&self, entropy: f64) -> Rating {
+        if entropy < self.bands.weak {
+            Rating::VeryWeak
+        } else if entropy < self.bands.strong {
+            Rating::Weak
+        } else if entropy < self.bands.very_strong {
+            Rating::Strong
+        } else {
+            Rating::VeryStrong
+        }
+    }
+
+    /// Every rule `password`, rated `rating`, breaks.
+    pub fn check(&self, password: &str, rating: Rating) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if rating < self.min_rating {
+            violations.push(Violation::Rating {
+                rating,
+                minimum: self.min_rating,
+            });
+        }
+        for class in &self.require {
+            if !password.chars().any(|c| class.contains(c)) {
+                violations.push(Violation::MissingClass(*class));
+            }
+        }
+        let length = grapheme_len(password);
+        if length < self.min_length {
+            violations.push(Violation::TooShort {
+                length,
+                minimum: self.min_length,
+            });
+        }
+        violations
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy_matches_fixed_bands() {
+        let policy = Policy::default();
+        assert_eq!(policy.rate(34.9), Rating::VeryWeak);
+        assert_eq!(policy.rate(35.0), Rating::Weak);
+        assert_eq!(policy.rate(60.0), Rating::Strong);
+        assert_eq!(policy.rate(120.0), Rating::VeryStrong);
+        assert!(policy.check("anything", Rating::Strong).is_empty());
+        assert_eq!(policy.check("anything", Rating::Weak).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = Policy::parse(
+            "min_rating = \"weak\"\n\
+             min_length = 10\n\
+             require = [\"uppercase\", \"digit\"]\n\
+             [bands]\n\
+             weak = 20.0\n",
+        )
+        .unwrap();
+        assert_eq!(policy.bands.strong, 60.0);
+        assert_eq!(policy.rate(25.0), Rating::Weak);
+        assert_eq!(
+            policy.check("password", policy.rate(25.0)),
+            vec![
+                Violation::MissingClass(CharClass::Uppercase),
+                Violation::MissingClass(CharClass::Digit),
+                Violation::TooShort {
+                    length: 8,
+                    minimum: 10
+                },
+            ]
+        );
+
+        assert!(matches!(
+            Policy::parse("[bands]\nweak = 70.0\n"),
+            Err(PolicyError::Bands)
+        ));
+        assert!(matches!(
+            Policy::parse("min_lenght = 3\n"),
+            Err(PolicyError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+//! Commands understood by the interactive prompt.
+//!
+//! When standard input is a terminal, a line starting with `:` is a command
+//! rather than a candidate password; [`HELP`] lists them. Piped input never
+//! goes through here, so its output is unchanged.
+use crate::Config;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub const HELP: &str = "\
+:explain            full match trace of the last candidate
+:set leet on|off    also case, double, dates, combine and decompose
+:set digits 0..8    digits appended to words, 0 to turn it off
+:load FILE          add a dictionary
+:stats              candidates checked and accepted so far
+:help               this list";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Explain,
+    Set(Setting),
+    Load(String),
+    Stats,
+    Help,
+}
+
+/// A strategy switched with `:set`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Leet(bool),
+    Case(bool),
+    Double(bool),
+    Dates(bool),
+    Combine(bool),
+    Decompose(bool),
+    /// Up to this many digits appended; 0 turns digit-append off.
+    Digits(usize),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CommandError {
+    #[error("unknown command \"{0}\", try :help")]
+    Unknown(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+}
+
+/// Whether `line` is a command rather than a candidate.
+pub fn is_command(line: &str) -> bool {
+    line.starts_with(':')
+}
+
+fn switch(value: Option<&str>) -> Result<bool, CommandError> {
+    match value {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err(CommandError::Usage(":set NAME on|off")),
+    }
+}
+
+impl FromStr for Setting {
+    type Err = CommandError;
+
+    /// Parses the arguments of `:set`, e.g. `leet on` or `digits 3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let (name, value) = (words.next(), words.next());
+        if words.next().is_some() {
+            return Err(CommandError::Usage(":set NAME VALUE"));
+        }
+        match name {
+            Some("leet") => switch(value).map(Setting::Leet),
+            Some("case") => switch(value).map(Setting::Case),
+            Some("double") => switch(value).map(Setting::Double),
+            Some("dates") => switch(value).map(Setting::Dates),
+            Some("combine") => switch(value).map(Setting::Combine),
+            Some("decompose") => switch(value).map(Setting::Decompose),
+            Some("digits") => value
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n <= 8)
+                .map(Setting::Digits)
+                .ok_or(CommandError::Usage(":set digits 0..8")),
+            _ => Err(CommandError::Usage(":set NAME VALUE")),
+        }
+    }
+}
+
+impl Setting {
+    pub fn apply(&self, config: &mut Config) {
+        match *self {
+            Setting::Leet(on) => config.leet = on,
+            Setting::Case(on) => config.case_sensitive = on,
+            Setting::Double(on) => config.double_check = on,
+            Setting::Dates(on) => config.dates = on,
+            Setting::Combine(on) => config.combine = on,
+            Setting::Decompose(on) => config.decompose = on,
+            Setting::Digits(0) => config.digit_append = false,
+            Setting::Digits(n) => {
+                config.digit_append = true;
+                config.num_digits = n;
+            }
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        match name {
+            ":explain" if rest.is_empty() => Ok(Command::Explain),
+            ":stats" if rest.is_empty() => Ok(Command::Stats),
+            ":help" if rest.is_empty() => Ok(Command::Help),
+            ":set" => rest.parse().map(Command::Set),
+            ":load" if !rest.is_empty() => Ok(Command::Load(rest.to_string())),
+            ":load" => Err(CommandError::Usage(":load FILE")),
+            _ => Err(CommandError::Unknown(s.to_string())),
+        }
+    }
+}
+
+/// Counts shown by `:stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub checked: usize,
+    /// Candidates rated strong enough, or meeting the `--policy`.
+    pub accepted: usize,
+    /// Whether a `--policy` decides which candidates are accepted.
+    pub policy: bool,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} candidate(s) checked, {} {}",
+            self.checked,
+            self.accepted,
+            if self.policy {
+                "meet the policy"
+            } else {
+                "strong"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(":explain".parse(), Ok(Command::Explain));
+        assert_eq!(
+            ":set leet on".parse(),
+            Ok(Command::Set(Setting::Leet(true)))
+        );
+        assert_eq!(
+            ":set  digits 3 ".parse(),
+            Ok(Command::Set(Setting::Digits(3)))
+        );
+        assert_eq!(
+            ":load words.txt".parse(),
+            Ok(Command::Load("words.txt".to_string()))
+        );
+        assert!(matches!(
+            ":set digits 9".parse::<Command>(),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(matches!(
+            ":set leet maybe".parse::<Command>(),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(matches!(
+            ":quit".parse::<Command>(),
+            Err(CommandError::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let mut config = Config::new();
+        Setting::Digits(3).apply(&mut config);
+        Setting::Leet(true).apply(&mut config);
+        assert!(config.digit_append && config.leet);
+        assert_eq!(config.num_digits, 3);
+        Setting::Digits(0).apply(&mut config);
+        assert!(!config.digit_append);
+    }
+
+    #[test]
+    fn test_stats_wording() {
+        let mut stats = Stats {
+            checked: 3,
+            accepted: 1,
+            policy: false,
+        };
+        assert_eq!(stats.to_string(), "3 candidate(s) checked, 1 strong");
+        stats.policy = true;
+        assert_eq!(
+            stats.to_string(),
+            "3 candidate(s) checked, 1 meet the policy"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use super::{first_index, GuessStrategy, Hit};
+use std::collections::HashMap;
+
+/// Tries every dictionary word as-is, in file order.
+#[derive(Default)]
+pub struct BasicStrategy {
+    /// Each word and the position of its first occurrence.
+    ranks: HashMap<String, usize>,
+}
+
+impl BasicStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl GuessStrategy for BasicStrategy {
+    fn name(&self) -> &str {
+        "basic"
+    }
+
+    fn prepare(&mut self, passwords: &[String]) {
+        self.ranks = first_index(passwords.iter().cloned());
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        passwords.len() as u64
+    }
+
+    fn find(&self, password: &str, _passwords: &[String]) -> Option<Hit> {
+        self.ranks.get(password).map(|&i| Hit {
+            guess: i as u64 + 1,
+            word: i,
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use super::{first_index, GuessStrategy, Hit};
+use crate::get_letter_count;
+use std::collections::HashMap;
+
+/// `--case`: tries every upper/lower case variant of each word.
+#[derive(Default)]
+pub struct CaseStrategy {
+    /// Upper-cased word and the position of its first occurrence.
+    folded: HashMap<String, usize>,
+    /// Guesses spent up to and including each word.
+    scales: Vec<u64>,
+}
+
+/// Case variants of `pwd` other than the word itself.
+pub(super) fn variants(pwd: &str) -> u64 {
+    let letter_count = get_letter_count(pwd) as u32;
+    2_u64.checked_pow(letter_count).map_or(u64::MAX, |x| x - 1)
+}
+
+impl CaseStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl GuessStrategy for CaseStrategy {
+    fn name(&self) -> &str {
+        "case"
+    }
+
+    fn prepare(&mut self, passwords: &[String]) {
+        self.folded = first_index(passwords.iter().map(|pwd| pwd.to_uppercase()));
+        self.scales = passwords
+            .iter()
+            .scan(0, |acc: &mut u64, pwd| {
+                *acc = acc.saturating_add(variants(pwd));
+                Some(*acc)
+            })
+            .collect();
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        passwords
+            .iter()
+            .fold(0, |acc: u64, pwd| acc.saturating_add(variants(pwd)))
+    }
+
+    fn find(&self, password: &str, _passwords: &[String]) -> Option<Hit> {
+        let i = *self.folded.get(&password.to_uppercase())?;
+        Some(Hit {
+            guess: self.scales[i],
+            word: i,
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use super::case;
+use super::{DigitAppendStrategy, GuessStrategy, Hit, LeetStrategy};
+use crate::Config;
+
+/// `--combine`: chains the enabled transforms the way cracking rules do,
+/// first a case change, then leet substitutions, then a digit suffix.
+///
+/// Every step may also leave the word alone, so a word's chain costs the
+/// product of the step sizes (`2^letters` case variants, the leet variants
+/// plus the word, the digit strings plus no suffix), less the plain word
+/// that the basic match already tried.
+pub struct CombinedStrategy {
+    case: bool,
+    leet: Option<LeetStrategy>,
+    digits: Option<DigitAppendStrategy>,
+}
+
+impl CombinedStrategy {
+    /// Chains whichever of `--case`, `--leet` and `--digit-append` are on.
+    pub fn from_config(config: &Config) -> Self {
+        CombinedStrategy {
+            case: config.case_sensitive,
+            leet: config
+                .leet
+                .then(|| LeetStrategy::with_table(config.leet_table.clone().unwrap_or_default())),
+            digits: config
+                .digit_append
+                .then(|| DigitAppendStrategy::new(config.num_digits)),
+        }
+    }
+
+    /// Whether a digit string may follow `pwd`; as with `--digit-append`,
+    /// not after a word that already ends in a digit.
+    fn takes_digits(&self, pwd: &str) -> bool {
+        self.digits.is_some() && pwd.chars().last().is_some_and(|c| !c.is_ascii_digit())
+    }
+
+    /// Guesses in the chain built from `pwd`, without the plain word.
+    fn chain_size(&self, pwd: &str) -> u64 {
+        let mut size: u64 = 1;
+        if self.case {
+            size = size.saturating_mul(case::variants(pwd).saturating_add(1));
+        }
+        if let Some(leet) = &self.leet {
+            size = size.saturating_mul(leet.variants(pwd).saturating_add(1));
+        }
+        if let Some(digits) = self.digits.as_ref().filter(|_| self.takes_digits(pwd)) {
+            size = size.saturating_mul(digits.per_word().saturating_add(1));
+        }
+        size - 1
+    }
+
+    /// Whether `stem`, the candidate without its digit suffix, is a case and
+    /// leet variant of `pwd`.
+    fn matches(&self, stem: &[char], pwd: &str) -> bool {
+        let fold = |chars: &mut Vec<char>| {
+            if self.case {
+                *chars = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+            }
+        };
+        let mut stem = stem.to_vec();
+        let mut word: Vec<char> = pwd.chars().collect();
+        fold(&mut stem);
+        fold(&mut word);
+        match &self.leet {
+            Some(leet) => leet.matches(&stem, &word),
+            None => stem == word,
+        }
+    }
+}
+
+impl GuessStrategy for CombinedStrategy {
+    fn name(&self) -> &str {
+        "combined"
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        passwords
+            .iter()
+            .fold(0, |acc: u64, pwd| acc.saturating_add(self.chain_size(pwd)))
+    }
+
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit> {
+        let target: Vec<char> = password.chars().collect();
+        let trailing = target
+            .iter()
+            .rev()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let max_suffix = self
+            .digits
+            .as_ref()
+            .map_or(0, |digits| trailing.min(digits.num_digits()));
+        let mut password_scale: u64 = 0;
+        for (i, pwd) in passwords.iter().enumerate() {
+            let size = self.chain_size(pwd);
+            if size == 0 {
+                continue;
+            }
+            password_scale = password_scale.saturating_add(size);
+            let suffixes = if self.takes_digits(pwd) {
+                max_suffix
+            } else {
+                0
+            };
+            if (0..=suffixes).any(|k| self.matches(&target[..target.len() - k], pwd)) {
+                return Some(Hit {
+                    guess: password_scale,
+                    word: i,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let config = Config {
+            case_sensitive: true,
+            leet: true,
+            digit_append: true,
+            num_digits: 3,
+            ..Config::default()
+        };
+        let combined = CombinedStrategy::from_config(&config);
+        let passwords = vec!["monkey".to_string(), "password".to_string()];
+
+        // monkey: 2^6 case * (2 * 2) leet (o, e) * 1111 digits
+        assert_eq!(combined.chain_size("monkey"), 64 * 4 * 1111 - 1);
+        let hit = combined.find("P@ssw0rd123", &passwords).unwrap();
+        let expected = combined.chain_size("monkey") + combined.chain_size("password");
+        assert_eq!(
+            hit,
+            Hit {
+                guess: expected,
+                word: 1
+            }
+        );
+        assert!(combined.find("P@ssw0rd12345", &passwords).is_none());
+        assert!(combined.find("M0NKEY", &passwords).is_some());
+    }
+}
@@ -0,0 +1,163 @@
+use super::{GuessStrategy, Hit};
+use std::collections::HashMap;
+
+const FIRST_YEAR: u32 = 1900;
+const LAST_YEAR: u32 = 2100;
+
+/// `--dates`: appends or prepends a year (1900..=2100), a DDMM or MMDD stamp,
+/// or a full YYYYMMDD date to each word.
+///
+/// Every word costs the same number of guesses: all stamps as a suffix,
+/// then all stamps as a prefix. Stamps are tried years first, then DDMM,
+/// MMDD and YYYYMMDD; a stamp that belongs to more than one family (e.g.
+/// "1912") is only tried once.
+pub struct DateStrategy {
+    stamps: HashMap<String, u64>,
+}
+
+fn is_leap_year(year: u32) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: Option<u32>, month: u32) -> u32 {
+    match month {
+        2 => match year {
+            Some(year) if !is_leap_year(year) => 28,
+            _ => 29,
+        },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateStrategy {
+    pub fn new() -> Self {
+        let mut ordered = Vec::new();
+        for year in FIRST_YEAR..=LAST_YEAR {
+            ordered.push(format!("{:04}", year));
+        }
+        for month in 1..=12 {
+            for day in 1..=days_in_month(None, month) {
+                ordered.push(format!("{:02}{:02}", day, month));
+            }
+        }
+        for month in 1..=12 {
+            for day in 1..=days_in_month(None, month) {
+                ordered.push(format!("{:02}{:02}", month, day));
+            }
+        }
+        for year in FIRST_YEAR..=LAST_YEAR {
+            for month in 1..=12 {
+                for day in 1..=days_in_month(Some(year), month) {
+                    ordered.push(format!("{:04}{:02}{:02}", year, month, day));
+                }
+            }
+        }
+
+        let mut stamps = HashMap::with_capacity(ordered.len());
+        for stamp in ordered {
+            let next = stamps.len() as u64 + 1;
+            stamps.entry(stamp).or_insert(next);
+        }
+        DateStrategy { stamps }
+    }
+
+    /// Position of `stamp` in the order stamps are tried, starting at 1.
+    pub fn stamp_rank(&self, stamp: &str) -> Option<u64> {
+        self.stamps.get(stamp).copied()
+    }
+
+    /// Guesses spent on one word: every stamp as a suffix and as a prefix.
+    fn per_word(&self) -> u64 {
+        2 * self.stamps.len() as u64
+    }
+
+    /// Position of the stamp that turns `pwd` into `password`, counted from
+    /// the first guess for this word.
+    fn position(&self, password: &str, pwd: &str) -> Option<u64> {
+        let suffix = password
+            .strip_prefix(pwd)
+            .and_then(|stamp| self.stamps.get(stamp));
+        if let Some(guess) = suffix {
+            return Some(*guess);
+        }
+        password
+            .strip_suffix(pwd)
+            .and_then(|stamp| self.stamps.get(stamp))
+            .map(|guess| self.stamps.len() as u64 + guess)
+    }
+}
+
+impl Default for DateStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuessStrategy for DateStrategy {
+    fn name(&self) -> &str {
+        "dates"
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        (passwords.len() as u64).saturating_mul(self.per_word())
+    }
+
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit> {
+        let mut password_scale: u64 = 0;
+        for (i, pwd) in passwords.iter().enumerate() {
+            if let Some(guess) = self.position(password, pwd) {
+                return Some(Hit {
+                    guess: password_scale + guess,
+                    word: i,
+                });
+            }
+            password_scale += self.per_word();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_families() {
+        let dates = DateStrategy::new();
+        // 201 years, 366 DDMM, 366 MMDD minus overlaps, 73414 full dates
+        assert_eq!(dates.stamps["1900"], 1);
+        assert_eq!(dates.stamps["2100"], 201);
+        assert_eq!(dates.stamps["0101"], 202);
+        assert!(dates.stamps.contains_key("2902"));
+        assert!(dates.stamps.contains_key("20000229"));
+        assert!(!dates.stamps.contains_key("19000229"));
+        assert!(!dates.stamps.contains_key("3102"));
+        // "1912" is a year first, so it keeps the year's position
+        assert_eq!(dates.stamps["1912"], 1912 - 1900 + 1);
+    }
+
+    #[test]
+    fn test_prefix_and_suffix() {
+        let dates = DateStrategy::new();
+        let passwords = vec!["pass".to_string(), "summer".to_string()];
+        let per_word = dates.per_word();
+        let stamps = dates.stamps.len() as u64;
+
+        assert_eq!(
+            dates.find("pass1900", &passwords).map(|hit| hit.guess),
+            Some(1)
+        );
+        assert_eq!(
+            dates.find("1900pass", &passwords).map(|hit| hit.guess),
+            Some(stamps + 1)
+        );
+        assert_eq!(
+            dates.find("summer2019", &passwords).map(|hit| hit.guess),
+            Some(per_word + 120)
+        );
+        assert!(dates.find("summer0412", &passwords).is_some());
+        assert!(dates.find("summer19871", &passwords).is_none());
+        assert!(dates.find("summer", &passwords).is_none());
+    }
+}
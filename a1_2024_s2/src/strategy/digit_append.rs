@@ -0,0 +1,68 @@
+use super::{GuessStrategy, Hit};
+
+const POWER_TABLE: [u64; 7] = [10, 100, 1000, 10000, 100000, 1000000, 10000000];
+
+/// `--digit-append N`: appends every 1..=N digit string to each word that
+/// does not already end in a digit.
+pub struct DigitAppendStrategy {
+    num_digits: usize,
+}
+
+impl DigitAppendStrategy {
+    pub fn new(num_digits: usize) -> Self {
+        DigitAppendStrategy { num_digits }
+    }
+
+    pub(super) fn num_digits(&self) -> usize {
+        self.num_digits
+    }
+
+    /// Digit strings tried after each word.
+    pub(super) fn per_word(&self) -> u64 {
+        POWER_TABLE.iter().take(self.num_digits).sum()
+    }
+
+    /// Words the strategy appends to, with their index; an empty word ends
+    /// the walk early.
+    fn candidates(passwords: &[String]) -> impl Iterator<Item = (usize, &String)> {
+        passwords
+            .iter()
+            .enumerate()
+            .map_while(|(i, pwd)| pwd.chars().last().map(|c| (i, pwd, c)))
+            .filter(|(_, _, last_char)| !last_char.is_ascii_digit())
+            .map(|(i, pwd, _)| (i, pwd))
+    }
+}
+
+impl GuessStrategy for DigitAppendStrategy {
+    fn name(&self) -> &str {
+        "digit-append"
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        Self::candidates(passwords).count() as u64 * self.per_word()
+    }
+
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit> {
+        let mut password_scale = 0;
+        for (i, pwd) in Self::candidates(passwords) {
+            // 只有以字典词开头、剩余部分全为数字的候选才可能匹配
+            let suffix = password
+                .strip_prefix(pwd.as_str())
+                .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()));
+            if let Some(suffix) = suffix {
+                let width = suffix.len();
+                if width <= self.num_digits && width <= POWER_TABLE.len() {
+                    let shorter: u64 = POWER_TABLE.iter().take(width - 1).sum();
+                    let value: u64 = suffix.parse().ok()?;
+                    return Some(Hit {
+                        guess: password_scale + shorter + value + 1,
+                        word: i,
+                    });
+                }
+            }
+            password_scale += self.per_word();
+        }
+        None
+    }
+}
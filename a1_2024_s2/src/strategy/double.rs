@@ -0,0 +1,106 @@
+use super::{first_index, GuessStrategy, Hit};
+use std::collections::HashMap;
+
+/// `--double`: tries every ordered pair of dictionary words.
+#[derive(Default)]
+pub struct DoubleStrategy {
+    /// Each word and the position of its first occurrence.
+    ranks: HashMap<String, usize>,
+    /// The same words, for finding which of them start a candidate.
+    prefixes: PrefixTrie,
+    words: u64,
+}
+
+/// Character trie marking where each dictionary word ends.
+#[derive(Default)]
+struct PrefixTrie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    /// First position of the word ending at this node.
+    word: Option<usize>,
+}
+
+impl PrefixTrie {
+    fn new(words: &[String]) -> Self {
+        let mut trie = PrefixTrie {
+            nodes: vec![TrieNode::default()],
+        };
+        for (i, word) in words.iter().enumerate() {
+            let mut node = 0;
+            for c in word.chars() {
+                node = match trie.nodes[node].children.get(&c) {
+                    Some(&next) => next,
+                    None => {
+                        trie.nodes.push(TrieNode::default());
+                        let next = trie.nodes.len() - 1;
+                        trie.nodes[node].children.insert(c, next);
+                        next
+                    }
+                };
+            }
+            trie.nodes[node].word.get_or_insert(i);
+        }
+        trie
+    }
+
+    /// Byte length and first position of every word that `text` starts with,
+    /// shortest first.
+    fn prefixes_of(&self, text: &str) -> Vec<(usize, usize)> {
+        let Some(root) = self.nodes.first() else {
+            return Vec::new();
+        };
+        let mut found: Vec<_> = root.word.map(|i| (0, i)).into_iter().collect();
+        let mut node = 0;
+        for (offset, c) in text.char_indices() {
+            let Some(&next) = self.nodes[node].children.get(&c) else {
+                break;
+            };
+            node = next;
+            if let Some(i) = self.nodes[node].word {
+                found.push((offset + c.len_utf8(), i));
+            }
+        }
+        found
+    }
+}
+
+impl DoubleStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl GuessStrategy for DoubleStrategy {
+    fn name(&self) -> &str {
+        "double"
+    }
+
+    fn prepare(&mut self, passwords: &[String]) {
+        self.ranks = first_index(passwords.iter().cloned());
+        self.prefixes = PrefixTrie::new(passwords);
+        self.words = passwords.len() as u64;
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        let n = passwords.len() as u64;
+        n.saturating_mul(n)
+    }
+
+    fn find(&self, password: &str, _passwords: &[String]) -> Option<Hit> {
+        // 第一个词越靠前越先被猜到
+        let (i, j) = self
+            .prefixes
+            .prefixes_of(password)
+            .into_iter()
+            .filter_map(|(len, i)| Some((i, *self.ranks.get(&password[len..])?)))
+            .min()?;
+        Some(Hit {
+            guess: i as u64 * self.words + j as u64 + 1,
+            word: i,
+        })
+    }
+}
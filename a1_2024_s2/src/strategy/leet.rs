@@ -0,0 +1,247 @@
+use super::{GuessStrategy, Hit};
+use std::collections::HashMap;
+use std::fs;
+use thiserror::Error;
+
+/// Built-in substitutions; every character of the value is one choice.
+const BUILTIN: [(char, &str); 22] = [
+    ('a', "4@"),
+    ('b', "68"),
+    ('e', "3"),
+    ('g', "69"),
+    ('i', "1!"),
+    ('l', "1"),
+    ('o', "0"),
+    ('s', "5$"),
+    ('t', "7+"),
+    ('x', "%"),
+    ('z', "2"),
+    ('A', "4@"),
+    ('B', "68"),
+    ('E', "3"),
+    ('G', "69"),
+    ('I', "1!"),
+    ('L', "1"),
+    ('O', "0"),
+    ('S', "5$"),
+    ('T', "7+"),
+    ('X', "%"),
+    ('Z', "2"),
+];
+
+/// `from` may be written as any of `to`.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    from: Vec<char>,
+    to: Vec<Vec<char>>,
+}
+
+#[derive(Debug, Error)]
+pub enum LeetTableError {
+    #[error("unable to read leet table")]
+    Io(#[from] std::io::Error),
+    #[error("line {0} has no substitution")]
+    Line(usize),
+}
+
+/// Substitutions tried by `--leet`, the built-in ones unless `--leet-table`
+/// names a file.
+///
+/// Each line of a table file is a sequence followed by what it may be
+/// replaced with, separated by whitespace, e.g. `a 4 @` or `ph f`. Blank
+/// lines and lines starting with `#` are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeetTable {
+    /// Rules keyed by the first character they replace.
+    rules: HashMap<char, Vec<Rule>>,
+}
+
+impl LeetTable {
+    fn add(&mut self, from: &str, to: Vec<Vec<char>>) {
+        let from: Vec<char> = from.chars().collect();
+        let rules = self.rules.entry(from[0]).or_default();
+        match rules.iter_mut().find(|rule| rule.from == from) {
+            Some(rule) => rule.to.extend(to),
+            None => rules.push(Rule { from, to }),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LeetTableError> {
+        let mut table = LeetTable {
+            rules: HashMap::new(),
+        };
+        for (n, line) in text.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let Some(from) = tokens.next().filter(|t| !t.starts_with('#')) else {
+                continue;
+            };
+            let to: Vec<Vec<char>> = tokens.map(|t| t.chars().collect()).collect();
+            if to.is_empty() {
+                return Err(LeetTableError::Line(n + 1));
+            }
+            table.add(from, to);
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: &str) -> Result<Self, LeetTableError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Rules whose sequence starts at `word[index]`.
+    fn rules_at<'a>(&'a self, word: &'a [char], index: usize) -> impl Iterator<Item = &'a Rule> {
+        self.rules
+            .get(&word[index])
+            .into_iter()
+            .flatten()
+            .filter(move |rule| word[index..].starts_with(&rule.from))
+    }
+}
+
+impl Default for LeetTable {
+    fn default() -> Self {
+        let mut table = LeetTable {
+            rules: HashMap::new(),
+        };
+        for (from, to) in BUILTIN {
+            table.add(&from.to_string(), to.chars().map(|c| vec![c]).collect());
+        }
+        table
+    }
+}
+
+/// `--leet`: tries every combination of the substitutions in the leet table.
+pub struct LeetStrategy {
+    table: LeetTable,
+}
+
+impl LeetStrategy {
+    pub fn new() -> Self {
+        Self::with_table(LeetTable::default())
+    }
+
+    pub fn with_table(table: LeetTable) -> Self {
+        LeetStrategy { table }
+    }
+
+    /// Leet variants of `pwd` other than the word itself; zero when no
+    /// part of the word has a substitution. Each position is either kept or
+    /// replaced through one of the rules starting there, so with
+    /// single-character rules this is the product of the choices per
+    /// character.
+    pub(super) fn variants(&self, pwd: &str) -> u64 {
+        let word: Vec<char> = pwd.chars().collect();
+        // ways[i]: 从第 i 个字符开始的后缀有多少种写法
+        let mut ways = vec![0_u64; word.len() + 1];
+        ways[word.len()] = 1;
+        for i in (0..word.len()).rev() {
+            ways[i] = ways[i + 1];
+            for rule in self.table.rules_at(&word, i) {
+                let rest = ways[i + rule.from.len()];
+                ways[i] = ways[i].saturating_add(rest.saturating_mul(rule.to.len() as u64));
+            }
+        }
+        ways[0].saturating_sub(1)
+    }
+
+    /// Whether some variant of `word`, or the word itself, is `password`.
+    pub(super) fn matches(&self, password: &[char], word: &[char]) -> bool {
+        self.dfs(password, word, 0, 0)
+    }
+
+    /// Whether `word[index..]` can be written as `password[at..]`.
+    fn dfs(&self, password: &[char], word: &[char], index: usize, at: usize) -> bool {
+        if index == word.len() {
+            return at == password.len();
+        }
+
+        for rule in self.table.rules_at(word, index) {
+            for to in &rule.to {
+                // 剪枝：替换后与目标不同则不必继续
+                if password[at..].starts_with(to)
+                    && self.dfs(password, word, index + rule.from.len(), at + to.len())
+                {
+                    return true;
+                }
+            }
+        }
+
+        // 保持原始字符不变，继续搜索
+        password.get(at) == Some(&word[index]) && self.dfs(password, word, index + 1, at + 1)
+    }
+}
+
+impl Default for LeetStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuessStrategy for LeetStrategy {
+    fn name(&self) -> &str {
+        "leet"
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        passwords
+            .iter()
+            .fold(0, |acc: u64, pwd| acc.saturating_add(self.variants(pwd)))
+    }
+
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit> {
+        let target: Vec<char> = password.chars().collect();
+        let mut password_scale: u64 = 0;
+        for (i, pwd) in passwords.iter().enumerate() {
+            let variants = self.variants(pwd);
+            if variants == 0 {
+                continue;
+            }
+            password_scale = password_scale.saturating_add(variants);
+            let word: Vec<char> = pwd.chars().collect();
+            if self.matches(&target, &word) {
+                return Some(Hit {
+                    guess: password_scale,
+                    word: i,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_builtin_counts() {
+        let leet = LeetStrategy::new();
+        // a: 3 choices, s: 3 choices, p/w/r/d: none, o: 2
+        assert_eq!(leet.variants("password"), 3 * 3 * 3 * 2 - 1);
+        assert_eq!(leet.variants("xyz"), 2 * 2 - 1);
+        assert_eq!(leet.variants("dry"), 0);
+    }
+
+    #[test]
+    fn test_multi_character_rules() {
+        let table = LeetTable::parse("# phonetic\nph f\nck x\n\nh |-| #\n").unwrap();
+        let leet = LeetStrategy::with_table(table);
+        // "phreck": ph -> f, or p + (h | |-| | #), then ck kept or x
+        assert_eq!(leet.variants("phreck"), (1 + 1 + 2) * 2 - 1);
+
+        let passwords = words(&["ok", "phreck"]);
+        let hit = leet.find("frex", &passwords).unwrap();
+        assert_eq!(hit, Hit { guess: 7, word: 1 });
+        assert!(leet.find("p|-|reck", &passwords).is_some());
+        assert!(leet.find("p|-|re", &passwords).is_none());
+
+        assert!(matches!(
+            LeetTable::parse("a 4\nb\n"),
+            Err(LeetTableError::Line(2))
+        ));
+    }
+}
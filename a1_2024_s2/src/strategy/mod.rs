@@ -0,0 +1,128 @@
+//! Guessing strategies and the registry the analyzer walks through.
+//!
+//! Every strategy models one rule a cracker applies to the dictionary. The
+//! analyzer asks each registered strategy in turn whether it finds the
+//! candidate; if not, the strategy's full guess count is added to the running
+//! total before moving on to the next one.
+mod basic;
+mod case;
+mod combined;
+mod dates;
+mod digit_append;
+mod double;
+mod leet;
+
+pub use basic::BasicStrategy;
+pub use case::CaseStrategy;
+pub use combined::CombinedStrategy;
+pub use dates::DateStrategy;
+pub use digit_append::DigitAppendStrategy;
+pub use double::DoubleStrategy;
+pub use leet::{LeetStrategy, LeetTable, LeetTableError};
+
+use crate::Config;
+use std::collections::HashMap;
+
+/// Where a strategy found the candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    /// Guess number, counted from the first guess of the strategy.
+    pub guess: u64,
+    /// Index of the dictionary word the guess was built from; the first word
+    /// for `--double`.
+    pub word: usize,
+}
+
+/// A dictionary mutation rule.
+pub trait GuessStrategy: Send + Sync {
+    /// Name reported in [`crate::MatchReport::strategy`].
+    fn name(&self) -> &str;
+
+    /// Builds any lookup tables the strategy needs over `passwords`. Called
+    /// once with the loaded dictionary before `find` is used; strategies that
+    /// scan the list on every call need not override it.
+    fn prepare(&mut self, _passwords: &[String]) {}
+
+    /// Number of guesses spent when the whole dictionary has been tried
+    /// without a match.
+    fn guess_count(&self, passwords: &[String]) -> u64;
+
+    /// Guess on which `password` would be found, if this strategy finds it.
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit>;
+}
+
+/// Maps each key to the position of its first occurrence.
+pub(crate) fn first_index(keys: impl Iterator<Item = String>) -> HashMap<String, usize> {
+    let mut ranks = HashMap::new();
+    for (i, key) in keys.enumerate() {
+        ranks.entry(key).or_insert(i);
+    }
+    ranks
+}
+
+/// Ordered list of strategies tried after each other.
+#[derive(Default)]
+pub struct StrategyRegistry {
+    strategies: Vec<Box<dyn GuessStrategy>>,
+}
+
+impl StrategyRegistry {
+    pub fn new() -> Self {
+        StrategyRegistry {
+            strategies: Vec::new(),
+        }
+    }
+
+    /// Builds the built-in pipeline: the basic match followed by whichever of
+    /// `--case`, `--digit-append`, `--dates`, `--double` and `--leet` are
+    /// enabled, and finally the `--combine` chain.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = StrategyRegistry::new();
+        registry.register(Box::new(BasicStrategy::new()));
+        if config.case_sensitive {
+            registry.register(Box::new(CaseStrategy::new()));
+        }
+        if config.digit_append {
+            registry.register(Box::new(DigitAppendStrategy::new(config.num_digits)));
+        }
+        if config.dates {
+            registry.register(Box::new(DateStrategy::new()));
+        }
+        if config.double_check {
+            registry.register(Box::new(DoubleStrategy::new()));
+        }
+        if config.leet {
+            let table = config.leet_table.clone().unwrap_or_default();
+            registry.register(Box::new(LeetStrategy::with_table(table)));
+        }
+        if config.combine {
+            registry.register(Box::new(CombinedStrategy::from_config(config)));
+        }
+        registry
+    }
+
+    /// Appends `strategy` to the end of the pipeline.
+    pub fn register(&mut self, strategy: Box<dyn GuessStrategy>) -> &mut Self {
+        self.strategies.push(strategy);
+        self
+    }
+
+    /// Lets every strategy index `passwords`.
+    pub fn prepare(&mut self, passwords: &[String]) {
+        for strategy in &mut self.strategies {
+            strategy.prepare(passwords);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn GuessStrategy> {
+        self.strategies.iter().map(|s| s.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.strategies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strategies.is_empty()
+    }
+}
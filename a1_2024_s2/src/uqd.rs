@@ -0,0 +1,244 @@
+//! Precompiled `.uqd` dictionaries written by `uqentropy build-dict`.
+//!
+//! All integers are little-endian. The file is
+//!
+//! ```text
+//! magic "UQD\0" | version u16 | reserved u16 | source count u32 | word count u32
+//! per source: name length u32, name bytes, word count u32
+//! per word, in rank order: length u32, bytes
+//! CRC-32 of everything above, u32
+//! ```
+//!
+//! Words are stored already split, so loading only has to map the file, check
+//! the header and checksum and slice the words out. Each word is still held
+//! to the rules of a text word list, so a `.uqd` file is never more lenient.
+use crate::dictionary::{is_list_word, Dictionary};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// File name extension recognised by the command line.
+pub const EXTENSION: &str = "uqd";
+
+const MAGIC: &[u8; 4] = b"UQD\0";
+const VERSION: u16 = 1;
+
+#[derive(Debug, Error)]
+pub enum UqdError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("not a uqd file")]
+    BadMagic,
+    #[error("unsupported uqd version {0}")]
+    Version(u16),
+    #[error("checksum mismatch")]
+    Checksum,
+    #[error("truncated file")]
+    Truncated,
+    #[error("invalid UTF-8 in word list")]
+    Utf8,
+    #[error("invalid word in word list")]
+    InvalidWord,
+    #[error("no words in {0}")]
+    Empty(String),
+}
+
+/// Whether `path` names a `.uqd` file rather than a text word list.
+pub fn is_uqd(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext == EXTENSION)
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+/// Serialises `dictionary`, keeping its word order and sources.
+pub fn write(dictionary: &Dictionary, mut out: impl Write) -> io::Result<()> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&0_u16.to_le_bytes());
+    put_u32(&mut buf, dictionary.sources().len());
+    put_u32(&mut buf, dictionary.len());
+    for source in dictionary.sources() {
+        put_u32(&mut buf, source.name.len());
+        buf.extend_from_slice(source.name.as_bytes());
+        put_u32(&mut buf, source.len);
+    }
+    for word in dictionary.words() {
+        put_u32(&mut buf, word.len());
+        buf.extend_from_slice(word.as_bytes());
+    }
+    let checksum = crc32fast::hash(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    out.write_all(&buf)?;
+    out.flush()
+}
+
+/// Reads the fields of a `.uqd` image in order.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], UqdError> {
+        if self.data.len() < len {
+            return Err(UqdError::Truncated);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, UqdError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<usize, UqdError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, UqdError> {
+        let len = self.u32()?;
+        let bytes = self.bytes(len)?;
+        std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| UqdError::Utf8)
+    }
+}
+
+/// Parses a whole `.uqd` image.
+pub fn parse(data: &[u8]) -> Result<Dictionary, UqdError> {
+    if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+        return Err(UqdError::BadMagic);
+    }
+    let (body, checksum) = data.split_at(data.len().saturating_sub(4).max(MAGIC.len()));
+    let mut reader = Reader { data: body };
+    reader.bytes(MAGIC.len())?;
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(UqdError::Version(version));
+    }
+    if checksum.len() != 4
+        || crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().unwrap())
+    {
+        return Err(UqdError::Checksum);
+    }
+    reader.u16()?;
+    let source_count = reader.u32()?;
+    let word_count = reader.u32()?;
+    let mut sources = Vec::new();
+    for _ in 0..source_count {
+        let name = reader.string()?;
+        sources.push((name, reader.u32()?));
+    }
+    let mut words = Vec::new();
+    for _ in 0..word_count {
+        let word = reader.string()?;
+        if !is_list_word(&word) {
+            return Err(UqdError::InvalidWord);
+        }
+        words.push(word);
+    }
+    if sources.iter().map(|(_, len)| len).sum::<usize>() != words.len() {
+        return Err(UqdError::Truncated);
+    }
+    // 和文本字典一样，不能有空的来源文件或空字典
+    if let Some((name, _)) = sources.iter().find(|(_, len)| *len == 0) {
+        return Err(UqdError::Empty(format!("\"{}\"", name)));
+    }
+    if words.is_empty() {
+        return Err(UqdError::Empty("the file".to_string()));
+    }
+
+    let mut dictionary = Dictionary::new();
+    let mut rest = words.into_iter();
+    for (name, len) in sources {
+        dictionary.add_source(&name, rest.by_ref().take(len).collect());
+    }
+    Ok(dictionary)
+}
+
+/// Maps `path` into memory and parses it.
+pub fn load(path: &str) -> Result<Dictionary, UqdError> {
+    let file = File::open(path)?;
+    // SAFETY: the mapped bytes must not change while they are borrowed, but
+    // nothing stops another process truncating or rewriting the file in that
+    // time; a truncation can then fault on access and a rewrite can change
+    // words after the checksum was checked. uqentropy itself never writes to
+    // a file it is loading, and `parse` copies every word out so the mapping
+    // is dropped as soon as it returns.
+    let data = unsafe { Mmap::map(&file)? };
+    parse(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        dictionary.add_source("a.txt", vec!["password".into(), "123456".into()]);
+        dictionary.add_source("b.txt", vec!["qwerty".into()]);
+        dictionary
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut image = Vec::new();
+        write(&sample(), &mut image).unwrap();
+        let loaded = parse(&image).unwrap();
+        assert_eq!(loaded.words(), sample().words());
+        assert_eq!(loaded.sources(), sample().sources());
+        assert_eq!(loaded.source_of(2), Some("b.txt"));
+    }
+
+    #[test]
+    fn test_rejects_damaged_images() {
+        let mut image = Vec::new();
+        write(&sample(), &mut image).unwrap();
+
+        let mut flipped = image.clone();
+        flipped[20] ^= 1;
+        assert!(matches!(parse(&flipped), Err(UqdError::Checksum)));
+
+        let mut newer = image.clone();
+        newer[4] = 2;
+        assert!(matches!(parse(&newer), Err(UqdError::Version(2))));
+
+        assert!(matches!(parse(b"hello"), Err(UqdError::BadMagic)));
+        assert!(parse(&image[..image.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_what_a_text_list_would() {
+        for word in ["two words", "tab\tbed", "nul\0", ""] {
+            let mut dictionary = Dictionary::new();
+            dictionary.add_source("a.txt", vec!["password".into(), word.into()]);
+            let mut image = Vec::new();
+            write(&dictionary, &mut image).unwrap();
+            assert!(
+                matches!(parse(&image), Err(UqdError::InvalidWord)),
+                "{:?}",
+                word
+            );
+        }
+
+        let mut image = Vec::new();
+        write(&Dictionary::new(), &mut image).unwrap();
+        assert!(matches!(parse(&image), Err(UqdError::Empty(_))));
+
+        let mut dictionary = sample();
+        dictionary.add_source("empty.txt", Vec::new());
+        let mut image = Vec::new();
+        write(&dictionary, &mut image).unwrap();
+        assert_eq!(
+            parse(&image).unwrap_err().to_string(),
+            "no words in \"empty.txt\""
+        );
+    }
+}
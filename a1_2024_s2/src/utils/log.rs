@@ -1,31 +1,46 @@
-use env_logger;
-use log;
+use log::LevelFilter;
+use std::fmt;
 use std::fs;
-/// 初始化日志系统，创建带时间戳的日志文件
-pub fn init_logging() {
-    // 创建log目录（如果不存在）
-    fs::create_dir_all("log").expect("无法创建log目录");
+use std::io;
 
-    let the_time = chrono::Local::now().format("%Y_%m_%d_%H:%M:%S").to_string();
+/// Level used when only `--log-file` is given.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
 
-    // 构造日志文件路径
-    let log_file_path = format!("log/uqentropy_{}.log", the_time);
-
-    // 设置环境变量以配置env_logger
-    std::env::set_var("RUST_LOG", "debug");
+/// 初始化日志系统。`file` 和 `level` 都没有给出时不记录任何日志；
+/// 只给出 `level` 时写到标准错误。日志文件以追加方式打开，不会创建目录。
+pub fn init_logging(file: Option<&str>, level: Option<LevelFilter>) -> io::Result<()> {
+    if file.is_none() && level.is_none() {
+        return Ok(());
+    }
+    let mut builder = env_logger::Builder::new();
+    builder
+        .filter_level(level.unwrap_or(DEFAULT_LEVEL))
+        .format_timestamp_secs();
+    if let Some(path) = file {
+        let log_file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        builder.target(env_logger::Target::Pipe(Box::new(log_file)));
+    }
+    builder.init();
+    Ok(())
+}
 
-    // 初始化env_logger并设置输出到文件
-    let log_file = fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(&log_file_path)
-        .expect("无法创建日志文件");
+/// Logs a candidate password or dictionary line without its content.
+///
+/// Every log statement that mentions user-supplied text must wrap it in
+/// `Redacted`; only the length is written.
+pub struct Redacted<'a>(pub &'a str);
 
-    env_logger::builder()
-        .target(env_logger::Target::Pipe(Box::new(log_file)))
-        .format_timestamp_secs()
-        .init();
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted, {} chars>", self.0.chars().count())
+    }
+}
 
-    log::info!("日志系统已初始化，日志文件: {}", log_file_path);
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
 }
@@ -69,9 +69,10 @@ fn test_check_password_is_valid() {
     // 测试包含空字符控制字符的密码（这是不允许的）
     assert!(!check_password_is_valid("password\x00"));
 
-    // 测试包含非ASCII字符的密码
-    assert!(!check_password_is_valid("密码"));
-    assert!(!check_password_is_valid("password@测试"));
+    // 测试包含非ASCII字符的密码（支持UTF-8，但仍拒绝控制字符）
+    assert!(check_password_is_valid("密码"));
+    assert!(check_password_is_valid("password@测试"));
+    assert!(!check_password_is_valid("密码\u{85}"));
 }
 
 // 测试floor_to_one_decimal函数
@@ -201,3 +202,544 @@ fn test_cli_with_double_option() {
         ))
         .code(0);
 }
+
+// 测试Analyzer库接口 - 不打印，返回结构化结果
+#[test]
+fn test_analyzer_report() {
+    let passwords = vec!["password".to_string(), "123456".to_string()];
+    let config = Config {
+        case_sensitive: true,
+        ..Config::default()
+    };
+    let analyzer = Analyzer::new(passwords, config);
+
+    let report = analyzer.analyze("123456");
+    assert_eq!(report.strategy.as_deref(), Some("basic"));
+    assert_eq!(report.guess_number, 2);
+    assert_eq!(report.entropy, 2.0);
+
+    let report = analyzer.analyze("PassWord");
+    assert_eq!(report.strategy.as_deref(), Some("case"));
+    assert_eq!(report.guess_number, 2 + 255);
+
+    let report = analyzer.analyze("letmein");
+    assert!(!report.is_match());
+    assert_eq!(report.guess_number, 2 + 255);
+    assert_eq!(report.entropy, f64::MAX);
+}
+
+// 自定义猜测策略 - 注册在内置策略之后
+struct ReversedStrategy;
+
+impl GuessStrategy for ReversedStrategy {
+    fn name(&self) -> &str {
+        "reversed"
+    }
+
+    fn guess_count(&self, passwords: &[String]) -> u64 {
+        passwords.len() as u64
+    }
+
+    fn find(&self, password: &str, passwords: &[String]) -> Option<Hit> {
+        let reversed: String = password.chars().rev().collect();
+        passwords
+            .iter()
+            .position(|pwd| *pwd == reversed)
+            .map(|i| Hit {
+                guess: i as u64 + 1,
+                word: i,
+            })
+    }
+}
+
+#[test]
+fn test_analyzer_custom_strategy() {
+    let passwords = vec!["password".to_string(), "dragon".to_string()];
+    let mut analyzer = Analyzer::new(passwords, Config::default());
+    analyzer.register(Box::new(ReversedStrategy));
+    assert_eq!(analyzer.registry().len(), 2);
+
+    let report = analyzer.analyze("nogard");
+    assert_eq!(report.strategy.as_deref(), Some("reversed"));
+    assert_eq!(report.guess_number, 2 + 2);
+
+    let report = analyzer.analyze("letmein");
+    assert!(!report.is_match());
+    assert_eq!(report.guess_number, 4);
+}
+
+// CLI测试 - 测试使用--dates选项
+#[test]
+fn test_cli_with_dates_option() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--dates");
+    cmd.arg("testfiles/top25.passwords");
+
+    // 字典词加年份应被识别
+    cmd.write_stdin("1987password\n");
+
+    cmd.assert()
+        .stdout(predicate::str::contains(
+            "Candidate password would be matched on guess number",
+        ))
+        .stdout(predicate::str::contains(
+            "Password strength rating: very weak",
+        ))
+        .code(14);
+}
+
+// CLI测试 - 测试使用--keyboard选项（无需字典文件）
+#[test]
+fn test_cli_with_keyboard_option() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--keyboard");
+    cmd.arg("qwerty");
+
+    cmd.write_stdin("qwertyuiop\n1qaz2wsx\n");
+
+    cmd.assert()
+        .stdout(predicate::str::contains(
+            "Candidate password is a keyboard walk on the qwerty layout (1 turn)",
+        ))
+        .stdout(predicate::str::contains(
+            "Candidate password is a keyboard walk on the qwerty layout (2 turns)",
+        ))
+        .stdout(predicate::str::contains("Password strength rating: strong").not())
+        .code(14);
+}
+
+// CLI测试 - 测试使用未知键盘布局
+#[test]
+fn test_cli_with_unknown_keyboard() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--keyboard");
+    cmd.arg("colemak");
+    cmd.assert()
+        .stderr(predicate::str::contains("Usage: ./uqentropy"))
+        .code(2);
+}
+
+// CLI测试 - 测试使用--decompose选项
+#[test]
+fn test_cli_with_decompose_option() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--decompose");
+    cmd.arg("testfiles/top25.passwords");
+
+    cmd.write_stdin("password2019\n");
+
+    cmd.assert()
+        .stdout(predicate::str::contains(
+            "Candidate password decomposes into 2 segments (240 guesses)",
+        ))
+        .stdout(predicate::str::contains(
+            "  \"password\" dictionary: 1 guesses",
+        ))
+        .stdout(predicate::str::contains("  \"2019\" date: 120 guesses"))
+        .code(14);
+}
+
+// CLI测试 - 测试--help列出所有选项，和其他参数一起使用仍是用法错误
+#[test]
+fn test_cli_help() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--help");
+    cmd.assert()
+        .stdout(predicate::str::contains("--breach-db DIR"))
+        .stdout(predicate::str::contains("./uqentropy build-dict"))
+        .code(0);
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--help", "testfiles/top25.passwords"]);
+    cmd.assert()
+        .stderr(
+            "Usage: ./uqentropy [--leet] [--double] [--digit-append 1..8] [--case] [listfilename ...]\n",
+        )
+        .code(2);
+}
+
+// CLI测试 - 测试--format json输出每个候选一条记录
+#[test]
+fn test_cli_with_json_format() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--format");
+    cmd.arg("json");
+    cmd.arg("testfiles/top25.passwords");
+
+    cmd.write_stdin("abc123\nStrongPassword123!\n");
+
+    cmd.assert()
+        .stdout(
+            "{\"password\":\"abc123\",\"entropy\":3.0,\"rating\":\"very weak\",\
+             \"strategy\":\"basic\",\"guess_number\":4,\
+             \"source\":\"testfiles/top25.passwords\"}\n\
+             {\"password\":\"StrongPassword123!\",\"entropy\":117.9,\"rating\":\"strong\",\
+             \"strategy\":null,\"guess_number\":null,\"source\":null}\n",
+        )
+        .code(0);
+}
+
+// CLI测试 - 测试--format csv输出表头和记录
+#[test]
+fn test_cli_with_csv_format() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--format");
+    cmd.arg("csv");
+
+    cmd.write_stdin("password\n");
+
+    cmd.assert()
+        .stdout("password,entropy,rating,strategy,guess_number,source\npassword,37.6,weak,,,\n")
+        .code(14);
+}
+
+// CLI测试 - 测试--keyboard时记录里报告键盘路径的布局和转向次数
+#[test]
+fn test_cli_with_keyboard_records() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--keyboard", "qwerty", "--format", "json"]);
+    cmd.arg("testfiles/top25.passwords");
+
+    cmd.write_stdin("qwertyuiop\nabc123\n");
+
+    cmd.assert()
+        .stdout(
+            "{\"password\":\"qwertyuiop\",\"entropy\":11.9,\"rating\":\"very weak\",\
+             \"strategy\":\"keyboard-walk\",\"guess_number\":1944,\"source\":null,\
+             \"layout\":\"qwerty\",\"turns\":1}\n\
+             {\"password\":\"abc123\",\"entropy\":3.0,\"rating\":\"very weak\",\
+             \"strategy\":\"basic\",\"guess_number\":4,\
+             \"source\":\"testfiles/top25.passwords\",\"layout\":null,\"turns\":null}\n",
+        )
+        .code(14);
+}
+
+// CLI测试 - 测试--candidates配合--jobs多线程时输出顺序与单线程一致
+#[test]
+fn test_cli_with_candidates_and_jobs() {
+    let run = |jobs: &str| {
+        let mut cmd = cargo_bin_cmd!("uqentropy");
+        cmd.args([
+            "--case",
+            "--leet",
+            "--candidates",
+            "testfiles/top25.passwords",
+        ]);
+        cmd.args(["--jobs", jobs, "testfiles/top25.passwords"]);
+        let output = cmd.output().unwrap();
+        assert_eq!(output.status.code(), Some(14));
+        output.stdout
+    };
+    let serial = run("1");
+    assert_eq!(run("4"), serial);
+    let serial = String::from_utf8(serial).unwrap();
+    assert!(serial.ends_with("No strong password(s) have been identified\n"));
+}
+
+// CLI测试 - 测试--candidates文件无法打开
+#[test]
+fn test_cli_with_missing_candidates() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--candidates", "testfiles/no_such_file", "--jobs", "2"]);
+
+    cmd.assert()
+        .stderr(predicate::str::contains(
+            "unable to open file \"testfiles/no_such_file\" for reading",
+        ))
+        .code(20);
+}
+
+// CLI测试 - 测试build-dict生成的.uqd字典与文本字典结果一致
+#[test]
+fn test_cli_build_dict_round_trip() {
+    let output = std::env::temp_dir().join(format!("uqentropy_{}.uqd", std::process::id()));
+    let output = output.to_str().unwrap();
+    let sources = ["testfiles/top25.passwords", "testfiles/top10000.txt"];
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("build-dict").args(sources).args(["-o", output]);
+    cmd.assert().success();
+
+    let run = |lists: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("uqentropy");
+        cmd.args(["--case", "--format", "json"]).args(lists);
+        cmd.write_stdin("abc123\nDRAGON\nzaq12wsx\n");
+        cmd.output().unwrap()
+    };
+    let from_text = run(&sources);
+    let from_uqd = run(&[output]);
+    std::fs::remove_file(output).unwrap();
+    assert_eq!(from_uqd.stdout, from_text.stdout);
+    assert_eq!(from_uqd.status.code(), from_text.status.code());
+}
+
+// CLI测试 - 测试train训练的模型降低了常见写法的熵
+#[test]
+fn test_cli_train_and_markov() {
+    let model = std::env::temp_dir().join(format!("uqentropy_{}.model", std::process::id()));
+    let model = model.to_str().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["train", "testfiles/top10000.txt", "-o", model]);
+    cmd.assert().success();
+
+    // 不用模型时 10 * log2(36) = 51.6
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--markov", model]);
+    cmd.write_stdin("passwrd123\n");
+    let assert = cmd.assert();
+    std::fs::remove_file(model).unwrap();
+
+    assert
+        .stdout(predicate::str::contains("Markov model estimates"))
+        .stdout(predicate::str::contains(
+            "Password entropy calculated to be 44.8\n",
+        ))
+        .code(14);
+}
+
+// CLI测试 - 测试generate在相同种子下输出相同且每个密码都达到目标熵
+#[test]
+fn test_cli_generate() {
+    let run = |args: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("uqentropy");
+        cmd.args(["generate", "--bits", "80", "--seed", "42"])
+            .args(args);
+        let output = cmd.output().unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let passwords = run(&["--count", "5"]);
+    assert_eq!(passwords, run(&["--count", "5"]));
+    assert_eq!(passwords.lines().count(), 5);
+    for line in passwords.lines() {
+        let (password, entropy) = line.split_once('\t').unwrap();
+        assert!(entropy.parse::<f64>().unwrap() >= 80.0);
+        assert!(uqentropy::calculate_entropy(password) >= 80.0);
+    }
+
+    let passphrase = run(&["--words", "testfiles/top10000.txt"]);
+    assert!(passphrase.split('\t').next().unwrap().contains('-'));
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["generate", "--bits", "80", "--charset", "digit,emoji"]);
+    cmd.assert()
+        .stderr(predicate::str::starts_with("Usage: ./uqentropy generate"))
+        .code(2);
+
+    // 只有一个不同单词的单词表是无效的输入文件
+    let words = std::env::temp_dir().join(format!("uqentropy_{}.words", std::process::id()));
+    std::fs::write(&words, "dragon\ndragon\n").unwrap();
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["generate", "--bits", "80", "--words"])
+        .arg(&words);
+    let output = cmd.output().unwrap();
+    std::fs::remove_file(&words).unwrap();
+    assert_eq!(output.status.code(), Some(20));
+}
+
+// CLI测试 - 测试lint报告统计、问题位置和重叠，有问题的文件使退出码为20
+#[test]
+fn test_cli_lint() {
+    let list = std::env::temp_dir().join(format!("uqentropy_{}.lint", std::process::id()));
+    std::fs::write(&list, "letmein dragon\n\n  bad\x01\nletmein\n").unwrap();
+    let list = list.to_str().unwrap().to_string();
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["lint", "testfiles/top25.passwords", &list]);
+    let output = cmd.output().unwrap();
+    std::fs::remove_file(&list).unwrap();
+    assert_eq!(output.status.code(), Some(20));
+    let expected = format!(
+        "testfiles/top25.passwords: 25 token(s), 0 duplicate(s), 19 very weak\n\
+         {0}: 3 token(s), 1 duplicate(s), 3 very weak\n\
+         {0}: line 2: no passwords\n\
+         {0}: line 3, column 6: invalid character U+0001\n\
+         {0}: 2 token(s) also in testfiles/top25.passwords\n",
+        list
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "uqentropy: 1 file(s) failed lint\n"
+    );
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["lint", "testfiles/top25.passwords"]);
+    cmd.assert().code(0);
+}
+
+// CLI测试 - 测试build-dict缺少-o参数
+#[test]
+fn test_cli_build_dict_without_output() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["build-dict", "testfiles/top25.passwords"]);
+
+    cmd.assert()
+        .stderr("Usage: ./uqentropy build-dict listfilename ... -o output.uqd\n")
+        .code(2);
+}
+
+// CLI测试 - 测试--policy报告违反的规则并决定退出码
+#[test]
+fn test_cli_with_policy() {
+    let policy = std::env::temp_dir().join(format!("uqentropy_{}.toml", std::process::id()));
+    std::fs::write(
+        &policy,
+        "min_rating = \"weak\"\nmin_length = 10\nrequire = [\"digit\"]\n[bands]\nweak = 40.0\n",
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--policy").arg(&policy);
+    cmd.write_stdin("password\nlonger-passphrase7\n");
+    let assert = cmd.assert();
+    std::fs::remove_file(&policy).unwrap();
+
+    assert
+        .stdout(
+            "Welcome to UQEntropy!\n\
+             Written by @yaojun.\n\
+             Enter candidate passwords to check their strength.\n\
+             Password entropy calculated to be 37.6\n\
+             Password strength rating: very weak\n\
+             Policy violation: rated very weak but at least weak is required\n\
+             Policy violation: no digit character\n\
+             Policy violation: 8 characters long but at least 10 are required\n\
+             Password entropy calculated to be 109.5\n\
+             Password strength rating: strong\n",
+        )
+        .code(0);
+}
+
+// CLI测试 - 测试无效的策略文件
+#[test]
+fn test_cli_with_invalid_policy() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--policy", "testfiles/top25.passwords"]);
+
+    cmd.assert()
+        .stderr(predicate::str::starts_with(
+            "uqentropy: invalid policy file \"testfiles/top25.passwords\"",
+        ))
+        .code(20);
+}
+
+// CLI测试 - 测试中文和西里尔字母口令以及NFKC字典匹配
+#[test]
+fn test_cli_with_unicode_passwords() {
+    let list = std::env::temp_dir().join(format!("uqentropy_{}.txt", std::process::id()));
+    std::fs::write(&list, "пароль\npassword\n").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg(&list);
+    cmd.write_stdin("пароль\nｐａｓｓｗｏｒｄ\n我的密码很长\n");
+    let assert = cmd.assert();
+    std::fs::remove_file(&list).unwrap();
+
+    assert
+        .stdout(
+            "Welcome to UQEntropy!\n\
+             Written by @yaojun.\n\
+             Enter candidate passwords to check their strength.\n\
+             Candidate password would be matched on guess number 1\n\
+             Password entropy calculated to be 1.0\n\
+             Password strength rating: very weak\n\
+             Candidate password would be matched on guess number 2\n\
+             Password entropy calculated to be 2.0\n\
+             Password strength rating: very weak\n\
+             No match would be found after checking 2 passwords\n\
+             Password entropy calculated to be 70.6\n\
+             Password strength rating: strong\n",
+        )
+        .code(0);
+}
+
+// CLI测试 - 测试--leet-table加载多字符替换
+#[test]
+fn test_cli_with_leet_table() {
+    let table = std::env::temp_dir().join(format!("uqentropy_{}.leet", std::process::id()));
+    std::fs::write(&table, "ph f\nck x\no 0\n").unwrap();
+    // 25 + "password"的1种变体 + "monkey"的1种变体
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--leet-table").arg(&table);
+    cmd.arg("testfiles/top25.passwords");
+    cmd.write_stdin("m0nkey\n");
+    let assert = cmd.assert();
+    std::fs::remove_file(&table).unwrap();
+
+    assert
+        .stdout(predicate::str::contains(
+            "Candidate password would be matched on guess number 27\n",
+        ))
+        .code(14);
+}
+
+// CLI测试 - 测试--combine组合大小写、leet和数字后缀
+#[test]
+fn test_cli_with_combine_option() {
+    let run = |combine: bool| {
+        let mut cmd = cargo_bin_cmd!("uqentropy");
+        cmd.args(["--case", "--leet", "--digit-append", "3"]);
+        if combine {
+            cmd.arg("--combine");
+        }
+        cmd.arg("testfiles/top25.passwords");
+        cmd.write_stdin("P@ssw0rd123\n");
+        String::from_utf8(cmd.output().unwrap().stdout).unwrap()
+    };
+    assert!(run(false).contains("No match would be found"));
+    assert!(run(true).contains("Candidate password would be matched on guess number"));
+}
+
+// CLI测试 - 测试--breach-db中出现过的密码一律评为very weak
+#[test]
+fn test_cli_with_breach_db() {
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.arg("--breach-db").arg("testfiles/breach");
+    cmd.write_stdin("CorrectHorseBatteryStaple1!\n");
+    cmd.assert()
+        .stdout(predicate::str::contains(
+            "Candidate password appears 3 time(s) in the breach corpus\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Password strength rating: very weak\n",
+        ))
+        .code(14);
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--breach-db", "testfiles/no_such_dir"]);
+    cmd.assert()
+        .stderr("uqentropy: unable to open file \"testfiles/no_such_dir\" for reading\n")
+        .code(20);
+}
+
+// CLI测试 - 测试默认不写日志，--log-file中的候选密码和字典内容都被隐去
+#[test]
+fn test_cli_logging() {
+    let dir = std::env::temp_dir().join(format!("uqentropy_log_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let list = concat!(env!("CARGO_MANIFEST_DIR"), "/testfiles/top25.passwords");
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.current_dir(&dir).arg(list).write_stdin("hunter2\n");
+    cmd.assert().code(14);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.current_dir(&dir)
+        .args(["--log-file", "run.log", "--log-level", "debug", list])
+        .write_stdin("hunter2\n");
+    cmd.assert().code(14);
+    let log = std::fs::read_to_string(dir.join("run.log")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(log.contains("Evaluated <redacted, 7 chars>"));
+    assert!(!log.contains("hunter2"));
+
+    let mut cmd = cargo_bin_cmd!("uqentropy");
+    cmd.args(["--log-file", "testfiles/no_such_dir/run.log"]);
+    cmd.assert()
+        .stderr("uqentropy: unable to open file \"testfiles/no_such_dir/run.log\" for writing\n")
+        .code(20);
+}
@@ -1,4 +1,5 @@
 use std::process::exit;
+use uqexpr::error::{ExprError, UQExprError};
 use uqexpr::*;
 
 fn format_value(value: f64, significant_figures: u8) -> String {
@@ -9,7 +10,7 @@ fn format_value(value: f64, significant_figures: u8) -> String {
     let sig = significant_figures.max(1) as i32;
     let abs = value.abs();
 
-    let use_scientific = abs >= 10000.0 || abs < 0.0001;
+    let use_scientific = !(0.0001..10000.0).contains(&abs);
     if use_scientific {
         let decimals = (sig - 1).max(0) as usize;
         let s = format!("{:.*e}", decimals, value);
@@ -83,6 +84,14 @@ fn print_state(config: &Config) {
             );
         }
     }
+
+    // Only shown once a function has been defined
+    if !config.function_order.is_empty() {
+        println!("Functions:");
+        for name in &config.function_order {
+            println!("{}", config.functions[name]);
+        }
+    }
 }
 
 fn sync_loop_current_from_map(config: &mut Config, name: &str, value: f64) {
@@ -127,55 +136,32 @@ fn print_loop_definition_line(config: &Config, loop_var: &ForLoop) {
     );
 }
 
-fn handle_range_command(config: &mut Config, spec: &str) -> bool {
+fn handle_range_command(config: &mut Config, spec: &str) -> Result<(), ExprError> {
     let parts: Vec<&str> = spec.split(',').map(|s| s.trim()).collect();
-    if parts.len() != 4 {
-        eprintln!("Invalid command, expression or assignment operation detected");
-        return true;
-    }
-
     let name = parts[0];
-    if name.is_empty() {
-        eprintln!("Invalid command, expression or assignment operation detected");
-        return true;
+    if parts.len() != 4 || name.is_empty() {
+        return Err(ExprError::Invalid(
+            "@range needs a name, start, increment and end".to_string(),
+        ));
     }
 
-    let start: f64 = match parts[1].parse() {
-        Ok(v) => v,
-        Err(_) => {
-            eprintln!("Invalid command, expression or assignment operation detected");
-            return true;
-        }
-    };
-    let increment: f64 = match parts[2].parse() {
-        Ok(v) => v,
-        Err(_) => {
-            eprintln!("Invalid command, expression or assignment operation detected");
-            return true;
-        }
-    };
-    let end: f64 = match parts[3].parse() {
-        Ok(v) => v,
-        Err(_) => {
-            eprintln!("Invalid command, expression or assignment operation detected");
-            return true;
-        }
-    };
+    let start: f64 = parts[1]
+        .parse()
+        .map_err(|_| ExprError::Invalid(format!("Invalid number: {}", parts[1])))?;
+    let increment: f64 = parts[2]
+        .parse()
+        .map_err(|_| ExprError::Invalid(format!("Invalid number: {}", parts[2])))?;
+    let end: f64 = parts[3]
+        .parse()
+        .map_err(|_| ExprError::Invalid(format!("Invalid number: {}", parts[3])))?;
 
     if increment == 0.0 {
-        eprintln!("Invalid command, expression or assignment operation detected");
-        return true;
+        return Err(ExprError::Invalid("@range increment is zero".to_string()));
     }
-    if start < end {
-        if increment < 0.0 {
-            eprintln!("Invalid command, expression or assignment operation detected");
-            return true;
-        }
-    } else if start > end {
-        if increment > 0.0 {
-            eprintln!("Invalid command, expression or assignment operation detected");
-            return true;
-        }
+    if (start < end && increment < 0.0) || (start > end && increment > 0.0) {
+        return Err(ExprError::Invalid(
+            "@range increment moves away from the end".to_string(),
+        ));
     }
 
     if let Some(lv) = find_loop_var_mut(config, name) {
@@ -203,19 +189,24 @@ fn handle_range_command(config: &mut Config, spec: &str) -> bool {
         .find(|lv| lv.name == name)
         .expect("loop var just inserted");
     print_loop_definition_line(config, lv);
-    true
+    Ok(())
 }
 
-fn handle_loop_command(config: &mut Config, var_name: &str, expr: &str) -> bool {
+/// Runs `expr` once for each value of the loop variable. A failing iteration
+/// is reported on its own and the loop carries on.
+fn handle_loop_command(config: &mut Config, var_name: &str, expr: &str) -> Result<(), ExprError> {
     let var_name = var_name.trim();
     if var_name.is_empty() || expr.trim().is_empty() {
-        eprintln!("Invalid command, expression or assignment operation detected");
-        return true;
+        return Err(ExprError::Invalid(
+            "@loop needs a variable and an expression".to_string(),
+        ));
     }
 
     let Some(loop_idx) = find_loop_index(config, var_name) else {
-        eprintln!("Invalid command, expression or assignment operation detected");
-        return true;
+        return Err(ExprError::Invalid(format!(
+            "Unknown loop variable: {}",
+            var_name
+        )));
     };
 
     // Copy loop range parameters so we don't hold a mutable borrow while
@@ -225,6 +216,12 @@ fn handle_loop_command(config: &mut Config, var_name: &str, expr: &str) -> bool
         (lv.start, lv.increment, lv.end)
     };
 
+    // The body is parsed once and evaluated on every iteration; a body that
+    // does not parse still steps the loop variable through its range.
+    let trimmed_expr = expr.trim();
+    let parsed = Expr::parse(trimmed_expr);
+    let target = parsed.as_ref().ok().and_then(Expr::target);
+
     let sig = config.significant_figures;
     let eps = 1e-9;
     let mut cur = start;
@@ -244,14 +241,13 @@ fn handle_loop_command(config: &mut Config, var_name: &str, expr: &str) -> bool
         config.for_loop_struct_vec[loop_idx].current = cur;
         config.init_map.insert(var_name.to_string(), cur);
 
-        let trimmed_expr = expr.trim();
-        let is_assignment = trimmed_expr.contains('=');
-        let eval_res = { evaluate_expression(trimmed_expr, &mut config.init_map) };
+        let eval_res = match &parsed {
+            Ok(parsed) => evaluate_parsed(parsed, &mut config.init_map, &config.functions),
+            Err(err) => Err(err.clone()),
+        };
         match eval_res {
             Ok(result) => {
-                if is_assignment {
-                    let parts: Vec<&str> = trimmed_expr.split('=').collect();
-                    let lhs = parts.get(0).map(|s| s.trim()).unwrap_or("");
+                if let Some(lhs) = target {
                     println!(
                         "{} = {} when {} = {}",
                         lhs,
@@ -271,15 +267,106 @@ fn handle_loop_command(config: &mut Config, var_name: &str, expr: &str) -> bool
                     );
                 }
             }
-            Err(_) => {
-                eprintln!("Invalid command, expression or assignment operation detected");
-            }
+            Err(err) => report_invalid(&err),
         }
 
         cur += inc;
     }
 
-    true
+    Ok(())
+}
+
+/// Prints the required error line. Errors from calling or defining a
+/// function, such as a wrong argument count, are followed by the reason,
+/// which the plain line does not give.
+fn report_invalid(err: &ExprError) {
+    eprintln!("Invalid command, expression or assignment operation detected");
+    match err {
+        ExprError::Invalid(_) => {}
+        ExprError::Call(_) | ExprError::Definition(_) => eprintln!("uqexpr: {}", err),
+    }
+}
+
+/// Defines a function, or evaluates an expression or assignment and prints
+/// the result.
+fn handle_line(config: &mut Config, trimmed: &str) -> Result<(), ExprError> {
+    let expr = match Statement::parse(trimmed)? {
+        Statement::Define(function) => {
+            println!("{}", function);
+            config.define_function(function);
+            return Ok(());
+        }
+        Statement::Expr(expr) => expr,
+    };
+    let result = evaluate_parsed(&expr, &mut config.init_map, &config.functions)?;
+    let formatted_result = format_result(result, config.significant_figures);
+
+    // Check if it's an assignment
+    if let Some(var_name) = expr.target() {
+        // For assignments, print "variable = value"
+        println!("{} = {}", var_name, formatted_result);
+
+        // If this assignment targets a loop variable, update its current value.
+        sync_loop_current_from_map(config, var_name, result);
+    } else {
+        // For regular expressions, print "Result = value"
+        println!("Result = {}", formatted_result);
+    }
+    Ok(())
+}
+
+/// Runs one line of input that is not part of an @if or @while directive.
+fn handle_command(config: &mut Config, line: &str) -> Result<(), ExprError> {
+    let raw = line.trim_end();
+    if raw.trim().is_empty() {
+        return Ok(());
+    }
+
+    if raw.trim_start().starts_with('#') {
+        return Ok(());
+    }
+
+    // Commands must start at column 1.
+    if raw == "@print" {
+        print_state(config);
+        return Ok(());
+    }
+
+    if let Some(spec) = raw.strip_prefix("@range ") {
+        // Exactly one space after @range.
+        if spec.starts_with(' ') {
+            return Err(ExprError::Invalid("Invalid @range command".to_string()));
+        }
+        return handle_range_command(config, spec);
+    } else if raw.starts_with("@range") {
+        // Anything else that begins with @range is invalid.
+        return Err(ExprError::Invalid("Invalid @range command".to_string()));
+    }
+
+    if let Some(rest) = raw.strip_prefix("@loop ") {
+        // Exactly one space after @loop.
+        if rest.starts_with(' ') {
+            return Err(ExprError::Invalid("Invalid @loop command".to_string()));
+        }
+        let mut it = rest.splitn(2, char::is_whitespace);
+        let var = it.next().unwrap_or("").trim();
+        let expr = it.next().unwrap_or("");
+        return handle_loop_command(config, var, expr);
+    } else if raw.starts_with("@loop") {
+        return Err(ExprError::Invalid("Invalid @loop command".to_string()));
+    }
+
+    handle_line(config, raw.trim())
+}
+
+/// Reports a failing line outside any block with the required error line,
+/// and anything else, such as a failure inside a block, with its line number.
+fn feed_line(interpreter: &mut Interpreter, config: &mut Config, number: usize, line: &str) {
+    match interpreter.feed(config, number, line, &mut handle_command) {
+        Ok(()) => {}
+        Err(UQExprError::Expr(err)) => report_invalid(&err),
+        Err(err) => eprintln!("uqexpr: {}", err),
+    }
 }
 
 fn format_increment(value: f64) -> String {
@@ -287,7 +374,7 @@ fn format_increment(value: f64) -> String {
     if value.abs() >= 10000.0 && value.fract() == 0.0 {
         let abs = value.abs();
         let mut exponent = abs.log10().floor() as i32;
-        let mut mantissa = abs / 10_f64.powi(exponent);
+        let mantissa = abs / 10_f64.powi(exponent);
 
         // We print increments with a single significant digit (matches tests)
         let mut mantissa_int = mantissa.round() as i64;
@@ -377,212 +464,21 @@ fn main() {
         println!("Please enter your expressions and assignment operations to be evaluated.");
     }
 
-    // Handle file input if provided
+    // Handle file input if provided; both inputs share one interpreter so
+    // @if and @while blocks work the same way
+    let mut interpreter = Interpreter::new();
     if let Some(lines) = file_lines {
         // Process file content
-        for line in lines {
-            let raw = line.trim_end();
-            if raw.trim().is_empty() {
-                continue;
-            }
-
-            if raw.trim_start().starts_with('#') {
-                continue;
-            }
-
-            // Commands must start at column 1.
-            if raw == "@print" {
-                print_state(&config);
-                continue;
-            }
-
-            if raw.starts_with("@range ") {
-                // Exactly one space after @range.
-                if raw.as_bytes().get(7) == Some(&b' ') {
-                    eprintln!("Invalid command, expression or assignment operation detected");
-                    continue;
-                }
-                let spec = &raw[7..];
-                handle_range_command(&mut config, spec);
-                continue;
-            } else if raw.starts_with("@range") {
-                // Anything else that begins with @range is invalid.
-                if raw.starts_with('@') {
-                    eprintln!("Invalid command, expression or assignment operation detected");
-                    continue;
-                }
-            }
-
-            if raw.starts_with("@loop ") {
-                // Exactly one space after @loop.
-                if raw.as_bytes().get(6) == Some(&b' ') {
-                    eprintln!("Invalid command, expression or assignment operation detected");
-                    continue;
-                }
-                let rest = &raw[6..];
-                let mut it = rest.splitn(2, char::is_whitespace);
-                let var = it.next().unwrap_or("").trim();
-                let expr = it.next().unwrap_or("");
-                handle_loop_command(&mut config, var, expr);
-                continue;
-            } else if raw.starts_with("@loop") {
-                if raw.starts_with('@') {
-                    eprintln!("Invalid command, expression or assignment operation detected");
-                    continue;
-                }
-            }
-
-            let trimmed = raw.trim();
-            if !trimmed.is_empty() {
-                // Check if it's an assignment
-                if trimmed.contains('=') {
-                    match evaluate_expression(&trimmed, &mut config.init_map) {
-                        Ok(result) => {
-                            // For assignments, print "variable = value"
-                            let formatted_result =
-                                format_result(result, config.significant_figures);
-                            let parts: Vec<&str> = trimmed.split('=').collect();
-                            let var_name = parts[0].trim();
-                            println!("{} = {}", var_name, formatted_result);
-
-                            if config
-                                .for_loop_struct_vec
-                                .iter()
-                                .any(|lv| lv.name == var_name)
-                            {
-                                sync_loop_current_from_map(&mut config, var_name, result);
-                            }
-                        }
-                        Err(_) => {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                        }
-                    }
-                } else {
-                    match evaluate_expression(&trimmed, &mut config.init_map) {
-                        Ok(result) => {
-                            // For regular expressions, print "Result = value"
-                            let formatted_result =
-                                format_result(result, config.significant_figures);
-                            println!("Result = {}", formatted_result);
-                        }
-                        Err(_) => {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                        }
-                    }
-                }
-            }
+        for (i, line) in lines.iter().enumerate() {
+            feed_line(&mut interpreter, &mut config, i + 1, line);
         }
     } else {
         // Read from standard input
         use std::io::{self, BufRead};
         let stdin = io::stdin();
-        for line in stdin.lock().lines() {
+        for (i, line) in stdin.lock().lines().enumerate() {
             match line {
-                Ok(expression) => {
-                    let raw = expression.trim_end();
-                    if raw.trim().is_empty() {
-                        continue;
-                    }
-
-                    if raw.trim_start().starts_with('#') {
-                        continue;
-                    }
-
-                    if raw == "@print" {
-                        print_state(&config);
-                        continue;
-                    }
-
-                    if raw.starts_with("@range ") {
-                        if raw.as_bytes().get(7) == Some(&b' ') {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                            continue;
-                        }
-                        let spec = &raw[7..];
-                        handle_range_command(&mut config, spec);
-                        continue;
-                    } else if raw.starts_with("@range") {
-                        if raw.starts_with('@') {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                            continue;
-                        }
-                    }
-
-                    if raw.starts_with("@loop ") {
-                        if raw.as_bytes().get(6) == Some(&b' ') {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                            continue;
-                        }
-                        let rest = &raw[6..];
-                        let mut it = rest.splitn(2, char::is_whitespace);
-                        let var = it.next().unwrap_or("").trim();
-                        let expr = it.next().unwrap_or("");
-                        handle_loop_command(&mut config, var, expr);
-                        continue;
-                    } else if raw.starts_with("@loop") {
-                        if raw.starts_with('@') {
-                            eprintln!(
-                                "Invalid command, expression or assignment operation detected"
-                            );
-                            continue;
-                        }
-                    }
-
-                    let trimmed = raw.trim();
-                    if !trimmed.is_empty() {
-                        // Check if it's an assignment
-                        if trimmed.contains('=') {
-                            match evaluate_expression(&trimmed, &mut config.init_map) {
-                                Ok(result) => {
-                                    // For assignments, print "variable = value"
-                                    let formatted_result =
-                                        format_result(result, config.significant_figures);
-                                    let parts: Vec<&str> = trimmed.split('=').collect();
-                                    let var_name = parts[0].trim();
-                                    println!("{} = {}", var_name, formatted_result);
-
-                                    // If this assignment targets a loop variable, update its current value.
-                                    if config
-                                        .for_loop_struct_vec
-                                        .iter()
-                                        .any(|lv| lv.name == var_name)
-                                    {
-                                        sync_loop_current_from_map(&mut config, var_name, result);
-                                    }
-                                }
-                                Err(_) => {
-                                    eprintln!(
-                                        "Invalid command, expression or assignment operation detected"
-                                    );
-                                }
-                            }
-                        } else {
-                            match evaluate_expression(&trimmed, &mut config.init_map) {
-                                Ok(result) => {
-                                    // For regular expressions, print "Result = value"
-                                    let formatted_result =
-                                        format_result(result, config.significant_figures);
-                                    println!("Result = {}", formatted_result);
-                                }
-                                Err(_) => {
-                                    eprintln!(
-                                        "Invalid command, expression or assignment operation detected"
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
+                Ok(expression) => feed_line(&mut interpreter, &mut config, i + 1, &expression),
                 Err(err) => {
                     eprintln!("Error reading input: {}", err);
                     break;
@@ -590,6 +486,9 @@ fn main() {
             }
         }
     }
+    if let Err(err) = interpreter.finish() {
+        eprintln!("uqexpr: {}", err);
+    }
 
     // Print closing message
     println!("Thank you for using uqexpr.");
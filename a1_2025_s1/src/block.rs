@@ -0,0 +1,383 @@
+//! `@if expr` / `@else` / `@end` and `@while expr` / `@end` blocks.
+//!
+//! [`Interpreter::feed`] takes the input one line at a time, so a file and
+//! standard input are run the same way. A line outside any block goes
+//! straight to the line handler. A block is collected until its outermost
+//! `@end` arrives and then run. Directives and the lines inside a block may
+//! be indented.
+use crate::error::{ExprError, UQExprError, UQExprResult};
+use crate::{Config, Expr, evaluate_parsed};
+
+/// A `@while` whose body has run this many times is stopped.
+pub const MAX_WHILE_ITERATIONS: usize = 10_000;
+
+fn error(line: usize, message: impl ToString) -> UQExprError {
+    UQExprError::Script {
+        line,
+        message: message.to_string(),
+    }
+}
+
+/// The parsed condition if `line` is the directive `name`, e.g. `@if x > 1`.
+/// A condition that does not parse is kept as its error so the block still
+/// lines up with its `@end`.
+fn directive(line: &str, name: &str) -> Option<Result<Expr, ExprError>> {
+    let rest = line.strip_prefix(name)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let condition = rest.trim();
+    if condition.is_empty() {
+        return Some(Err(ExprError::Invalid(format!(
+            "{} needs a condition",
+            name
+        ))));
+    }
+    Some(Expr::parse(condition))
+}
+
+enum Node {
+    Line {
+        line: usize,
+        text: String,
+    },
+    If {
+        line: usize,
+        condition: Result<Expr, ExprError>,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    While {
+        line: usize,
+        condition: Result<Expr, ExprError>,
+        body: Vec<Node>,
+    },
+}
+
+impl Node {
+    fn run<F>(&self, config: &mut Config, handle: &mut F) -> UQExprResult<()>
+    where
+        F: FnMut(&mut Config, &str) -> Result<(), ExprError>,
+    {
+        match self {
+            Node::Line { line, text } => handle(config, text).map_err(|err| error(*line, err)),
+            Node::If {
+                line,
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if test(config, *line, condition)? {
+                    then
+                } else {
+                    otherwise
+                };
+                run_all(branch, config, handle)
+            }
+            Node::While {
+                line,
+                condition,
+                body,
+            } => {
+                let mut iterations = 0;
+                while test(config, *line, condition)? {
+                    if iterations == MAX_WHILE_ITERATIONS {
+                        return Err(error(
+                            *line,
+                            format!("@while stopped after {} iterations", iterations),
+                        ));
+                    }
+                    iterations += 1;
+                    run_all(body, config, handle)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn run_all<F>(nodes: &[Node], config: &mut Config, handle: &mut F) -> UQExprResult<()>
+where
+    F: FnMut(&mut Config, &str) -> Result<(), ExprError>,
+{
+    nodes.iter().try_for_each(|node| node.run(config, handle))
+}
+
+// Non-zero is true, as for the && and || operators
+fn test(
+    config: &mut Config,
+    line: usize,
+    condition: &Result<Expr, ExprError>,
+) -> UQExprResult<bool> {
+    let expr = condition.as_ref().map_err(|err| error(line, err))?;
+    let value = evaluate_parsed(expr, &mut config.init_map, &config.functions)
+        .map_err(|err| error(line, err))?;
+    Ok(value != 0.0)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    If,
+    While,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::If => "@if",
+            Kind::While => "@while",
+        }
+    }
+}
+
+// A block whose @end has not been read yet
+struct Open {
+    line: usize,
+    kind: Kind,
+    condition: Result<Expr, ExprError>,
+    body: Vec<Node>,
+    /// Lines after `@else`, once it has been read.
+    otherwise: Option<Vec<Node>>,
+}
+
+impl Open {
+    fn current(&mut self) -> &mut Vec<Node> {
+        self.otherwise.as_mut().unwrap_or(&mut self.body)
+    }
+
+    fn close(self) -> Node {
+        match self.kind {
+            Kind::If => Node::If {
+                line: self.line,
+                condition: self.condition,
+                then: self.body,
+                otherwise: self.otherwise.unwrap_or_default(),
+            },
+            Kind::While => Node::While {
+                line: self.line,
+                condition: self.condition,
+                body: self.body,
+            },
+        }
+    }
+}
+
+/// Runs input lines, collecting `@if` and `@while` blocks until they end.
+#[derive(Default)]
+pub struct Interpreter {
+    open: Vec<Open>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /// Handles line `number` (starting at 1) of the input. Every other line
+    /// is passed to `handle`, either now or when its block runs.
+    ///
+    /// An error stops the block being run; the next line starts afresh. A
+    /// line outside any block that `handle` rejects gives
+    /// [`UQExprError::Expr`]; every other error is a
+    /// [`UQExprError::Script`] naming its line.
+    pub fn feed<F>(
+        &mut self,
+        config: &mut Config,
+        number: usize,
+        raw: &str,
+        handle: &mut F,
+    ) -> UQExprResult<()>
+    where
+        F: FnMut(&mut Config, &str) -> Result<(), ExprError>,
+    {
+        let trimmed = raw.trim();
+        for (kind, name) in [(Kind::If, "@if"), (Kind::While, "@while")] {
+            if let Some(condition) = directive(trimmed, name) {
+                self.open.push(Open {
+                    line: number,
+                    kind,
+                    condition,
+                    body: Vec::new(),
+                    otherwise: None,
+                });
+                return Ok(());
+            }
+        }
+
+        match trimmed {
+            "@else" => match self.open.last_mut() {
+                Some(open) if open.kind == Kind::If && open.otherwise.is_none() => {
+                    open.otherwise = Some(Vec::new());
+                    Ok(())
+                }
+                _ => Err(error(number, "@else without @if")),
+            },
+            "@end" => {
+                let open = self
+                    .open
+                    .pop()
+                    .ok_or_else(|| error(number, "@end without @if or @while"))?;
+                self.add(config, open.close(), handle)
+            }
+            _ if self.open.is_empty() => handle(config, raw).map_err(UQExprError::Expr),
+            _ => {
+                let node = Node::Line {
+                    line: number,
+                    text: raw.trim_start().to_string(),
+                };
+                self.add(config, node, handle)
+            }
+        }
+    }
+
+    fn add<F>(&mut self, config: &mut Config, node: Node, handle: &mut F) -> UQExprResult<()>
+    where
+        F: FnMut(&mut Config, &str) -> Result<(), ExprError>,
+    {
+        match self.open.last_mut() {
+            Some(open) => {
+                open.current().push(node);
+                Ok(())
+            }
+            None => node.run(config, handle),
+        }
+    }
+
+    /// Call at the end of the input; fails if a block was never closed, and
+    /// discards it.
+    pub fn finish(&mut self) -> UQExprResult<()> {
+        match self.open.drain(..).next_back() {
+            Some(open) => Err(error(
+                open.line,
+                format!("{} has no matching @end", open.kind.name()),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate_expression;
+
+    // 依次输入各行，返回实际交给处理函数的行和遇到的错误
+    fn run(config: &mut Config, lines: &[&str]) -> (Vec<String>, Vec<String>) {
+        let mut handled = Vec::new();
+        let mut errors = Vec::new();
+        let mut handle = |config: &mut Config, line: &str| {
+            handled.push(line.to_string());
+            evaluate_expression(line, &mut config.init_map).map(|_| ())
+        };
+        let mut interpreter = Interpreter::new();
+        for (i, line) in lines.iter().enumerate() {
+            if let Err(err) = interpreter.feed(config, i + 1, line, &mut handle) {
+                errors.push(err.to_string());
+            }
+        }
+        if let Err(err) = interpreter.finish() {
+            errors.push(err.to_string());
+        }
+        (handled, errors)
+    }
+
+    #[test]
+    fn test_if_else() {
+        let mut config = Config::default();
+        let lines = ["x = 2", "@if x > 1", "  y = 1", "@else", "  y = 2", "@end"];
+        let (handled, errors) = run(&mut config, &lines);
+        assert_eq!(handled, ["x = 2", "y = 1"]);
+        assert!(errors.is_empty());
+        assert_eq!(config.init_map["y"], 1.0);
+
+        let lines = [
+            "@if x < 1",
+            "y = 3",
+            "@end",
+            "@if 0",
+            "@else",
+            "y = y + 1",
+            "@end",
+        ];
+        run(&mut config, &lines);
+        assert_eq!(config.init_map["y"], 2.0);
+    }
+
+    #[test]
+    fn test_nested_while() {
+        let mut config = Config::default();
+        let lines = [
+            "n = 0",
+            "evens = 0",
+            "@while n < 10",
+            "  n = n + 1",
+            "  @if n % 2 == 0",
+            "    evens = evens + 1",
+            "  @end",
+            "@end",
+        ];
+        let (handled, errors) = run(&mut config, &lines);
+        assert!(errors.is_empty());
+        assert_eq!(handled.len(), 2 + 10 + 5);
+        assert_eq!(config.init_map["n"], 10.0);
+        assert_eq!(config.init_map["evens"], 5.0);
+    }
+
+    #[test]
+    fn test_errors_carry_line_numbers() {
+        let mut config = Config::default();
+        for (lines, message) in [
+            (
+                &["@while 1", "@end"][..],
+                "line 1: @while stopped after 10000 iterations",
+            ),
+            (&["x = 1", "@else"], "line 2: @else without @if"),
+            (&["@while 0", "@else", "@end"], "line 2: @else without @if"),
+            (&["@end"], "line 1: @end without @if or @while"),
+            (
+                &["@if 1", "@while 0", "@end"],
+                "line 1: @if has no matching @end",
+            ),
+            (&["@if", "@end"], "line 1: @if needs a condition"),
+            (&["@if (1", "@end"], "line 1: Missing closing parenthesis"),
+            (
+                &["@if 1", "@if q", "@end", "@end"],
+                "line 2: Unknown variable: q",
+            ),
+        ] {
+            let (_, errors) = run(&mut config, lines);
+            assert_eq!(errors, [message], "{:?}", lines);
+        }
+
+        // 出错的块整体停止，后面的行照常处理
+        let (handled, errors) = run(&mut config, &["@if q", "a = 1", "@end", "b = 2"]);
+        assert_eq!(handled, ["b = 2"]);
+        assert_eq!(errors.len(), 1);
+        // 块外的错误交给调用者按原样报告
+        let (_, errors) = run(&mut config, &["1 +"]);
+        assert_eq!(errors, ["Invalid Expression: Unexpected end of expression"]);
+        // 只有指令本身才被识别
+        let (handled, _) = run(&mut config, &["@iffy", "@end x"]);
+        assert_eq!(handled, ["@iffy", "@end x"]);
+    }
+
+    #[test]
+    fn test_error_in_while_body() {
+        let mut config = Config::default();
+        let lines = [
+            "x = 1",
+            "@while x < 3",
+            "  x = x + 1",
+            "  bogus(",
+            "  y = 1",
+            "@end",
+        ];
+        let (handled, errors) = run(&mut config, &lines);
+        // 出错的那一行带上自己的行号，循环只报告一次就停止
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("line 4: "), "{}", errors[0]);
+        assert_eq!(handled, ["x = 1", "x = x + 1", "bogus("]);
+        assert_eq!(config.init_map["x"], 2.0);
+        assert!(!config.init_map.contains_key("y"));
+    }
+}
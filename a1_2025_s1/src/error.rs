@@ -12,4 +12,30 @@ pub enum UQExprError {
 
     #[error("Invalid Expression: {0}")]
     InvalidExpression(String),
+
+    /// A line outside any block that could not be run.
+    #[error("Invalid Expression: {0}")]
+    Expr(#[from] ExprError),
+
+    /// An `@if` or `@while` block that is malformed or cannot be run.
+    #[error("line {line}: {message}")]
+    Script { line: usize, message: String },
+}
+
+/// Why an expression, assignment or function definition was rejected.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExprError {
+    /// Malformed input, an unknown variable or an operation such as division
+    /// by zero.
+    #[error("{0}")]
+    Invalid(String),
+
+    /// A call to an unknown function, with the wrong number of arguments or
+    /// an argument outside its domain, or nested too deeply.
+    #[error("{0}")]
+    Call(String),
+
+    /// A function definition whose head or body is malformed.
+    #[error("{0}")]
+    Definition(String),
 }
@@ -0,0 +1,1048 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+use std::fmt;
+
+use crate::error::ExprError;
+
+// Token types for the expression parser
+#[derive(Debug)]
+enum Token {
+    Number(f64),
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    LeftParen,
+    RightParen,
+    Constant(String),
+    Variable(String),
+    Equals,
+    Comma,
+    EqualTo,
+    NotEqualTo,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
+    Question,
+    Colon,
+}
+
+// Tokenizer function to convert expression string into tokens
+fn tokenize(expression: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // Skip whitespace
+            ' ' | '\t' | '\n' | '\r' => continue,
+
+            // Arithmetic operators
+            // Arithmetic operators; the parser decides whether '-' negates
+            // or subtracts
+            '+' => tokens.push(Token::Plus),
+            '-' => tokens.push(Token::Minus),
+            '*' => tokens.push(Token::Multiply),
+            '/' => tokens.push(Token::Divide),
+            '%' => tokens.push(Token::Modulo),
+            '^' => tokens.push(Token::Power),
+
+            // Comparison and logical operators
+            '<' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::LessEqual),
+            '<' => tokens.push(Token::Less),
+            '>' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::GreaterEqual),
+            '>' => tokens.push(Token::Greater),
+            '!' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::NotEqualTo),
+            '!' => tokens.push(Token::Not),
+            '&' if chars.next_if_eq(&'&').is_some() => tokens.push(Token::And),
+            '|' if chars.next_if_eq(&'|').is_some() => tokens.push(Token::Or),
+            '?' => tokens.push(Token::Question),
+            ':' => tokens.push(Token::Colon),
+
+            // Parentheses
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+
+            // Equals operator for assignment, unless it is doubled
+            '=' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::EqualTo),
+            '=' => tokens.push(Token::Equals),
+
+            // Separates function arguments
+            ',' => tokens.push(Token::Comma),
+
+            // Numbers
+            '0'..='9' | '.' => {
+                let mut num_str = c.to_string();
+                while let Some(&next_c) = chars.peek() {
+                    if next_c.is_ascii_digit() || next_c == '.' {
+                        num_str.push(chars.next().unwrap());
+                    } else {
+                        break;
+                    }
+                }
+                match num_str.parse::<f64>() {
+                    Ok(num) => tokens.push(Token::Number(num)),
+                    Err(_) => {
+                        return Err(ExprError::Invalid(format!("Invalid number: {}", num_str)));
+                    }
+                }
+            }
+
+            // Constants, functions, and variables (a letter, then letters,
+            // digits or underscores as in is_valid_var_name)
+            'a'..='z' | 'A'..='Z' => {
+                let mut name = c.to_string();
+                while let Some(&next_c) = chars.peek() {
+                    if next_c.is_ascii_alphanumeric() || next_c == '_' {
+                        name.push(next_c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                // Check if it's a constant, otherwise treat as a variable; the
+                // parser turns a variable followed by '(' into a function call
+                let lowercase_name = name.to_lowercase();
+                match lowercase_name.as_str() {
+                    "pi" | "e" => tokens.push(Token::Constant(lowercase_name)),
+                    _ => tokens.push(Token::Variable(name)),
+                }
+            }
+
+            // Invalid character
+            _ => return Err(ExprError::Invalid(format!("Invalid character: {}", c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Least and most arguments of a built-in function, or `None` if `name` is
+/// not one. Built-in names are matched in lower case.
+fn builtin_arity(name: &str) -> Option<(usize, usize)> {
+    match name {
+        "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "exp" | "ln" | "sqrt" | "abs"
+        | "floor" | "ceil" | "round" => Some((1, 1)),
+        // log(x) is base 10, log(x, b) base b
+        "log" => Some((1, 2)),
+        "atan2" | "pow" | "hypot" => Some((2, 2)),
+        "min" | "max" => Some((2, usize::MAX)),
+        _ => None,
+    }
+}
+
+fn check_arity(name: &str, (min, max): (usize, usize), count: usize) -> Result<(), ExprError> {
+    if (min..=max).contains(&count) {
+        return Ok(());
+    }
+    let expected = if min == max {
+        format!("{} argument{}", min, if min == 1 { "" } else { "s" })
+    } else if max == usize::MAX {
+        format!("at least {} arguments", min)
+    } else {
+        format!("{} to {} arguments", min, max)
+    };
+    Err(ExprError::Call(format!(
+        "{} expects {}, got {}",
+        name, expected, count
+    )))
+}
+
+fn unknown_function(name: &str) -> ExprError {
+    ExprError::Call(format!("Unknown function: {}", name))
+}
+
+fn call_builtin(name: &str, args: &[f64]) -> Result<f64, ExprError> {
+    let arity = builtin_arity(name).ok_or_else(|| unknown_function(name))?;
+    check_arity(name, arity, args.len())?;
+    let x = args[0];
+    let undefined = || {
+        Err(ExprError::Call(format!(
+            "{} is undefined for {:?}",
+            name, args
+        )))
+    };
+    let value = match name {
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" | "acos" if !(-1.0..=1.0).contains(&x) => return undefined(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        "exp" => x.exp(),
+        "sqrt" if x < 0.0 => return undefined(),
+        "sqrt" => x.sqrt(),
+        "ln" | "log" if x <= 0.0 => return undefined(),
+        "ln" => x.ln(),
+        "log" => match args.get(1) {
+            Some(&base) if base <= 0.0 || base == 1.0 => return undefined(),
+            Some(&base) => x.log(base),
+            None => x.log10(),
+        },
+        "abs" => x.abs(),
+        "floor" => x.floor(),
+        "ceil" => x.ceil(),
+        "round" => x.round(),
+        "min" => args.iter().copied().fold(f64::INFINITY, f64::min),
+        "max" => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "atan2" => x.atan2(args[1]),
+        "pow" => x.powf(args[1]),
+        "hypot" => x.hypot(args[1]),
+        _ => return Err(unknown_function(name)),
+    };
+    Ok(value)
+}
+
+// Comparisons and logical operators yield 1 for true and 0 for false; any
+// non-zero value counts as true.
+fn truth(value: f64) -> bool {
+    value != 0.0
+}
+
+fn from_bool(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Precedence of `cond ? a : b`, which binds looser than any binary operator.
+const CONDITIONAL_PRECEDENCE: u8 = 1;
+/// Precedence of `-x` and `!x`: tighter than `*`, looser than `^`, so `-x^2`
+/// is `-(x^2)` while `2^-1` is `2^(-1)`.
+const UNARY_PRECEDENCE: u8 = 8;
+
+/// Binary operators, from the loosest binding to the tightest. All are
+/// left-associative except `^`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl BinOp {
+    // '^' is left to parse_exponent as it binds tighter than unary operators
+    fn from_token(token: &Token) -> Option<BinOp> {
+        match token {
+            Token::Or => Some(BinOp::Or),
+            Token::And => Some(BinOp::And),
+            Token::EqualTo => Some(BinOp::Eq),
+            Token::NotEqualTo => Some(BinOp::Ne),
+            Token::Less => Some(BinOp::Lt),
+            Token::LessEqual => Some(BinOp::Le),
+            Token::Greater => Some(BinOp::Gt),
+            Token::GreaterEqual => Some(BinOp::Ge),
+            Token::Plus => Some(BinOp::Add),
+            Token::Minus => Some(BinOp::Sub),
+            Token::Multiply => Some(BinOp::Mul),
+            Token::Divide => Some(BinOp::Div),
+            Token::Modulo => Some(BinOp::Mod),
+            _ => None,
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 2,
+            BinOp::And => 3,
+            BinOp::Eq | BinOp::Ne => 4,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 5,
+            BinOp::Add | BinOp::Sub => 6,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 7,
+            BinOp::Pow => 9,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Or => "||",
+            BinOp::And => "&&",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Pow => "^",
+        }
+    }
+
+    fn apply(self, left: f64, right: f64) -> Result<f64, ExprError> {
+        match self {
+            BinOp::Or => Ok(from_bool(truth(left) || truth(right))),
+            BinOp::And => Ok(from_bool(truth(left) && truth(right))),
+            BinOp::Eq => Ok(from_bool(left == right)),
+            BinOp::Ne => Ok(from_bool(left != right)),
+            BinOp::Lt => Ok(from_bool(left < right)),
+            BinOp::Le => Ok(from_bool(left <= right)),
+            BinOp::Gt => Ok(from_bool(left > right)),
+            BinOp::Ge => Ok(from_bool(left >= right)),
+            BinOp::Add => Ok(left + right),
+            BinOp::Sub => Ok(left - right),
+            BinOp::Mul => Ok(left * right),
+            BinOp::Div if right == 0.0 => Err(ExprError::Invalid("Division by zero".to_string())),
+            BinOp::Div => Ok(left / right),
+            BinOp::Mod if right == 0.0 => Err(ExprError::Invalid("Modulo by zero".to_string())),
+            BinOp::Mod => Ok(left % right),
+            BinOp::Pow => Ok(left.powf(right)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            UnaryOp::Neg => -value,
+            UnaryOp::Not => from_bool(!truth(value)),
+        }
+    }
+}
+
+/// A parsed expression or assignment.
+///
+/// [`Expr::parse`] only builds the tree; [`Expr::eval`] computes its value
+/// against an [`Env`] and never changes it, so a tree can be parsed once and
+/// evaluated many times, e.g. for every iteration of `@loop`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// `pi` or `e`, always lower case.
+    Constant(String),
+    Variable(String),
+    /// A call with its arguments; built-in names are stored in lower case.
+    Call(String, Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// `cond ? a : b`; only the chosen branch is evaluated.
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `name = expr`; only ever the root of a tree.
+    Assign(String, Box<Expr>),
+}
+
+/// Calls nested deeper than this fail, which stops runaway recursion.
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// User-defined functions by name.
+pub type Functions = HashMap<String, Function>;
+
+/// A user-defined function such as `f(x, y) = x^2 + y`.
+///
+/// Its parameters hide variables of the same name while the body is
+/// evaluated; other variables and functions are looked up when it is called,
+/// so a function may call itself or one defined after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+impl Function {
+    fn call(&self, env: &Env, args: Vec<f64>) -> Result<f64, ExprError> {
+        let arity = (self.params.len(), self.params.len());
+        check_arity(&self.name, arity, args.len())?;
+        if env.depth >= MAX_CALL_DEPTH {
+            return Err(ExprError::Call(format!(
+                "{} exceeded the limit of {} nested calls",
+                self.name, MAX_CALL_DEPTH
+            )));
+        }
+        let scope = Env {
+            params: &self.params,
+            args,
+            depth: env.depth + 1,
+            ..*env
+        };
+        self.body.eval(&scope)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}({}) = {}",
+            self.name,
+            self.params.join(", "),
+            self.body
+        )
+    }
+}
+
+/// One line of input: an expression or assignment, or a function definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Expr(Expr),
+    Define(Function),
+}
+
+impl Statement {
+    /// A line starting with `name(...) =` is a definition, anything else is
+    /// parsed by [`Expr::parse`]. Either must use up the whole line.
+    ///
+    /// A body that does not parse gives [`ExprError::Definition`], unless it
+    /// is a bad call such as a wrong argument count.
+    pub fn parse(source: &str) -> Result<Statement, ExprError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser::new(&tokens);
+        match parser.parse_definition_head()? {
+            Some((name, params)) => {
+                let body = parser
+                    .parse_expression()
+                    .and_then(|body| parser.end().map(|()| body))
+                    .map_err(|err| match err {
+                        ExprError::Invalid(message) => ExprError::Definition(message),
+                        err => err,
+                    })?;
+                Ok(Statement::Define(Function { name, params, body }))
+            }
+            None => {
+                let expr = parser.parse_assignment()?;
+                parser.end()?;
+                Ok(Statement::Expr(expr))
+            }
+        }
+    }
+}
+
+/// Variables and functions visible to [`Expr::eval`].
+pub struct Env<'a> {
+    variables: &'a HashMap<String, f64>,
+    functions: Option<&'a Functions>,
+    /// Parameters of the function being evaluated and their arguments.
+    params: &'a [String],
+    args: Vec<f64>,
+    depth: usize,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(variables: &'a HashMap<String, f64>) -> Self {
+        Env {
+            variables,
+            functions: None,
+            params: &[],
+            args: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn with_functions(self, functions: &'a Functions) -> Self {
+        Env {
+            functions: Some(functions),
+            ..self
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<f64> {
+        match self.params.iter().position(|param| param == name) {
+            Some(i) => Some(self.args[i]),
+            None => self.variables.get(name).copied(),
+        }
+    }
+}
+
+impl Expr {
+    /// Parses one expression or assignment, which must use up the whole
+    /// input.
+    pub fn parse(expression: &str) -> Result<Expr, ExprError> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser::new(&tokens);
+        let expr = parser.parse_assignment()?;
+        parser.end()?;
+        Ok(expr)
+    }
+
+    /// The variable assigned by this expression, if it is an assignment.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Expr::Assign(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Value of the expression; an assignment yields the assigned value
+    /// without storing it.
+    pub fn eval(&self, env: &Env) -> Result<f64, ExprError> {
+        match self {
+            Expr::Number(num) => Ok(*num),
+            Expr::Constant(name) => match name.as_str() {
+                "pi" => Ok(PI),
+                "e" => Ok(E),
+                _ => Err(ExprError::Invalid(format!("Unknown constant: {}", name))),
+            },
+            Expr::Variable(name) => env
+                .lookup(name)
+                .ok_or_else(|| ExprError::Invalid(format!("Unknown variable: {}", name))),
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match env.functions.and_then(|functions| functions.get(name)) {
+                    Some(function) => function.call(env, args),
+                    None => call_builtin(name, &args),
+                }
+            }
+            Expr::Unary(op, operand) => Ok(op.apply(operand.eval(env)?)),
+            // && and || skip the right operand once the left one decides
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), left, right) => {
+                let left = truth(left.eval(env)?);
+                if left == (*op == BinOp::Or) {
+                    return Ok(from_bool(left));
+                }
+                Ok(from_bool(truth(right.eval(env)?)))
+            }
+            Expr::Binary(op, left, right) => op.apply(left.eval(env)?, right.eval(env)?),
+            Expr::Conditional(cond, then, otherwise) => {
+                if truth(cond.eval(env)?) {
+                    then.eval(env)
+                } else {
+                    otherwise.eval(env)
+                }
+            }
+            Expr::Assign(_, value) => value.eval(env),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Assign(..) => 0,
+            Expr::Conditional(..) => CONDITIONAL_PRECEDENCE,
+            Expr::Binary(op, ..) => op.precedence(),
+            Expr::Unary(..) => UNARY_PRECEDENCE,
+            _ => u8::MAX,
+        }
+    }
+}
+
+// Prints with the fewest parentheses that parse back to the same tree.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(num) => write!(f, "{}", num),
+            Expr::Constant(name) | Expr::Variable(name) => f.write_str(name),
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                f.write_str(")")
+            }
+            Expr::Assign(name, value) => write!(f, "{} = {}", name, value),
+            Expr::Unary(op, operand) => {
+                f.write_str(op.symbol())?;
+                write_operand(f, operand, operand.precedence() < UNARY_PRECEDENCE)
+            }
+            Expr::Binary(op, left, right) => {
+                let prec = op.precedence();
+                // ^ 是右结合的，右边可以直接跟一元运算符
+                let (left_parens, right_parens) = if *op == BinOp::Pow {
+                    (
+                        left.precedence() <= prec,
+                        right.precedence() < UNARY_PRECEDENCE,
+                    )
+                } else {
+                    (left.precedence() < prec, right.precedence() <= prec)
+                };
+                write_operand(f, left, left_parens)?;
+                write!(f, " {} ", op.symbol())?;
+                write_operand(f, right, right_parens)
+            }
+            Expr::Conditional(cond, then, otherwise) => {
+                write_operand(f, cond, cond.precedence() <= CONDITIONAL_PRECEDENCE)?;
+                write!(f, " ? {} : {}", then, otherwise)
+            }
+        }
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Expr, parens: bool) -> fmt::Result {
+    if parens {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+fn missing_paren() -> ExprError {
+    ExprError::Invalid("Missing closing parenthesis".to_string())
+}
+
+fn invalid_params(name: &str) -> ExprError {
+    ExprError::Definition(format!("Invalid parameter list for {}", name))
+}
+
+// Parser struct to hold the token iterator
+struct Parser<'a> {
+    tokens: std::iter::Peekable<std::slice::Iter<'a, Token>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Parser {
+            tokens: tokens.iter().peekable(),
+        }
+    }
+
+    // Fail if any tokens are left over
+    fn end(&mut self) -> Result<(), ExprError> {
+        match self.tokens.next() {
+            Some(token) => Err(ExprError::Invalid(format!("Unexpected token: {:?}", token))),
+            None => Ok(()),
+        }
+    }
+
+    // Parse a primary expression (number, constant, function call, or parenthesized expression)
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        match self.tokens.next() {
+            Some(Token::Number(num)) => Ok(Expr::Number(*num)),
+
+            Some(Token::Constant(name)) => Ok(Expr::Constant(name.clone())),
+
+            Some(Token::LeftParen) => {
+                let expr = self.parse_expression()?;
+                if let Some(Token::RightParen) = self.tokens.next() {
+                    Ok(expr)
+                } else {
+                    Err(missing_paren())
+                }
+            }
+
+            Some(Token::Variable(name)) => {
+                if let Some(Token::LeftParen) = self.tokens.peek() {
+                    self.tokens.next(); // Consume the ( token
+                    return self.parse_call(name);
+                }
+                Ok(Expr::Variable(name.clone()))
+            }
+
+            Some(token) => Err(ExprError::Invalid(format!("Unexpected token: {:?}", token))),
+            None => Err(ExprError::Invalid(
+                "Unexpected end of expression".to_string(),
+            )),
+        }
+    }
+
+    // Parse the arguments of a function call: name(expression, ...)
+    fn parse_call(&mut self, name: &str) -> Result<Expr, ExprError> {
+        let mut args = Vec::new();
+        if let Some(Token::RightParen) = self.tokens.peek() {
+            self.tokens.next();
+        } else {
+            loop {
+                args.push(self.parse_expression()?);
+                match self.tokens.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RightParen) => break,
+                    _ => return Err(missing_paren()),
+                }
+            }
+        }
+
+        // Built-in calls are checked here, before anything is evaluated
+        let lowercase_name = name.to_lowercase();
+        match builtin_arity(&lowercase_name) {
+            Some(arity) => {
+                check_arity(&lowercase_name, arity, args.len())?;
+                Ok(Expr::Call(lowercase_name, args))
+            }
+            None => Ok(Expr::Call(name.to_string(), args)),
+        }
+    }
+
+    // Parse exponentiation, which is right-associative: 2^3^2 is 2^(3^2)
+    fn parse_exponent(&mut self) -> Result<Expr, ExprError> {
+        let base = self.parse_primary()?;
+
+        if let Some(Token::Power) = self.tokens.peek() {
+            self.tokens.next(); // Consume the ^ token
+            let exponent = self.parse_unary()?;
+            return Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+
+        Ok(base)
+    }
+
+    // Parse negation and logical not
+    fn parse_unary(&mut self) -> Result<Expr, ExprError> {
+        let op = match self.tokens.peek() {
+            Some(Token::Minus) => UnaryOp::Neg,
+            Some(Token::Not) => UnaryOp::Not,
+            _ => return self.parse_exponent(),
+        };
+        self.tokens.next(); // Consume the operator
+        let operand = self.parse_unary()?;
+        Ok(Expr::Unary(op, Box::new(operand)))
+    }
+
+    // Parse left-associative binary operators binding at least as tightly
+    // as min_precedence
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ExprError> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(op) = self
+            .tokens
+            .peek()
+            .and_then(|token| BinOp::from_token(token))
+        {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.tokens.next(); // Consume the operator
+            let right = self.parse_binary(op.precedence() + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    // Parse the head of a function definition, `name(param, ...) =`, if the
+    // tokens start with one; otherwise nothing is consumed
+    fn parse_definition_head(&mut self) -> Result<Option<(String, Vec<String>)>, ExprError> {
+        let mut peek_iter = self.tokens.clone();
+        let Some(Token::Variable(name)) = peek_iter.next() else {
+            return Ok(None);
+        };
+        if !matches!(peek_iter.next(), Some(Token::LeftParen)) {
+            return Ok(None);
+        }
+        let mut list = Vec::new();
+        loop {
+            match peek_iter.next() {
+                Some(Token::RightParen) => break,
+                Some(token) => list.push(token),
+                None => return Ok(None),
+            }
+        }
+        if !matches!(peek_iter.next(), Some(Token::Equals)) {
+            return Ok(None);
+        }
+        self.tokens = peek_iter;
+
+        if builtin_arity(&name.to_lowercase()).is_some() {
+            return Err(ExprError::Definition(format!(
+                "Cannot redefine built-in function {}",
+                name
+            )));
+        }
+        let mut params: Vec<String> = Vec::new();
+        if list.len() % 2 == 0 && !list.is_empty() {
+            return Err(invalid_params(name));
+        }
+        for (i, token) in list.into_iter().enumerate() {
+            match token {
+                Token::Comma if i % 2 == 1 => {}
+                Token::Variable(param) if i % 2 == 0 && !params.contains(param) => {
+                    params.push(param.clone());
+                }
+                _ => return Err(invalid_params(name)),
+            }
+        }
+        Ok(Some((name.clone(), params)))
+    }
+
+    // Parse assignment expressions: variable = expression
+    fn parse_assignment(&mut self) -> Result<Expr, ExprError> {
+        // First check if we have an assignment
+        if let Some(Token::Variable(name)) = self.tokens.peek() {
+            // Look ahead to see if there's an equals sign
+            let mut peek_iter = self.tokens.clone();
+            peek_iter.next(); // Skip the variable
+            if let Some(Token::Equals) = peek_iter.peek() {
+                // It's an assignment: consume the variable and equals sign
+                self.tokens.next(); // Consume the variable
+                self.tokens.next(); // Consume the equals sign
+
+                // Parse the expression on the right-hand side
+                let value = self.parse_expression()?;
+                return Ok(Expr::Assign(name.clone(), Box::new(value)));
+            }
+        }
+
+        // Not an assignment, parse as regular expression
+        self.parse_expression()
+    }
+
+    // Parse a full expression: binary operators, then an optional
+    // `? a : b`, which groups to the right
+    fn parse_expression(&mut self) -> Result<Expr, ExprError> {
+        let cond = self.parse_binary(CONDITIONAL_PRECEDENCE + 1)?;
+
+        if let Some(Token::Question) = self.tokens.peek() {
+            self.tokens.next(); // Consume the ? token
+            let then = self.parse_expression()?;
+            if !matches!(self.tokens.next(), Some(Token::Colon)) {
+                return Err(ExprError::Invalid(
+                    "Missing : in conditional expression".to_string(),
+                ));
+            }
+            let otherwise = self.parse_expression()?;
+            return Ok(Expr::Conditional(
+                Box::new(cond),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+
+        Ok(cond)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<f64, ExprError> {
+        let variables = HashMap::from([("x".to_string(), 2.0)]);
+        Expr::parse(expression)?.eval(&Env::new(&variables))
+    }
+
+    #[test]
+    fn test_parse_then_eval() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("2^3^2"), Ok(512.0));
+        assert_eq!(eval("-x^2"), Ok(-4.0));
+        assert_eq!(eval("exp(0) + sin(0)"), Ok(1.0));
+        assert_eq!(eval("y = x * 3"), Ok(6.0));
+        assert!(eval("q + 1").is_err());
+        assert!(eval("1 / (x - 2)").is_err());
+        assert!(eval("(1 + 2").is_err());
+        // 完整的表达式后面不能再有别的记号
+        for source in ["1 2 3", "x ? 1 : 2 : 9", "y = x)", "sin(x) x"] {
+            assert!(
+                matches!(Expr::parse(source), Err(ExprError::Invalid(_))),
+                "{}",
+                source
+            );
+            assert!(Statement::parse(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        assert_eq!(eval("cos(0) + tan(0) + abs(-3)"), Ok(4.0));
+        assert_eq!(eval("sqrt(16) + ln(e) + log(1000)"), Ok(8.0));
+        assert_eq!(eval("log(8, 2)"), Ok(3.0));
+        assert_eq!(eval("min(3, -x, 7) + max(1, x)"), Ok(0.0));
+        assert_eq!(eval("pow(x, 10) + hypot(3, 4)"), Ok(1029.0));
+        assert_eq!(eval("atan2(1, 1) * 4"), Ok(PI));
+        assert_eq!(eval("floor(2.5) + ceil(2.5) + round(2.5)"), Ok(8.0));
+        assert_eq!(eval("SQRT(4)"), Ok(2.0));
+        assert!(eval("sqrt(-1)").is_err());
+        assert!(eval("ln(0)").is_err());
+        assert!(eval("log(8, 1)").is_err());
+        assert!(eval("asin(2)").is_err());
+        assert_eq!(eval("f(1)"), Err(unknown_function("f")));
+        assert!(eval("min(1, 2").is_err());
+    }
+
+    #[test]
+    fn test_arity_errors() {
+        for (source, message) in [
+            ("sin(1, 2)", "sin expects 1 argument, got 2"),
+            ("atan2(1)", "atan2 expects 2 arguments, got 1"),
+            ("max(1)", "max expects at least 2 arguments, got 1"),
+            ("log(1, 2, 3)", "log expects 1 to 2 arguments, got 3"),
+            ("cos()", "cos expects 1 argument, got 0"),
+        ] {
+            // 参数个数在解析时就检查，不需要求值
+            assert_eq!(
+                Expr::parse(source),
+                Err(ExprError::Call(message.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignment_target() {
+        let expr = Expr::parse("y = x + 1").unwrap();
+        assert_eq!(expr.target(), Some("y"));
+        assert_eq!(Expr::parse("x + 1").unwrap().target(), None);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for (source, printed) in [
+            ("1+2*3", "1 + 2 * 3"),
+            ("(1+2)*3", "(1 + 2) * 3"),
+            ("1-(2-3)", "1 - (2 - 3)"),
+            ("(1-2)-3", "1 - 2 - 3"),
+            ("2^(3^2)", "2 ^ 3 ^ 2"),
+            ("(2^3)^2", "(2 ^ 3) ^ 2"),
+            ("-x^2", "-x ^ 2"),
+            ("(-x)^2", "(-x) ^ 2"),
+            ("2^-x", "2 ^ -x"),
+            ("-(1+x)", "-(1 + x)"),
+            ("!(x<1)||x%2==0&&1", "!(x < 1) || x % 2 == 0 && 1"),
+            ("(x>1?1:0)?x:(1?2:3)", "(x > 1 ? 1 : 0) ? x : 1 ? 2 : 3"),
+            ("y = sin(PI/2) - x", "y = sin(pi / 2) - x"),
+            ("Max(1,-x,hypot(3,4))", "max(1, -x, hypot(3, 4))"),
+        ] {
+            assert_eq!(Expr::parse(source).unwrap().to_string(), printed);
+            assert_eq!(eval(printed), eval(source));
+        }
+    }
+
+    fn define(functions: &mut Functions, source: &str) {
+        match Statement::parse(source).unwrap() {
+            Statement::Define(function) => {
+                functions.insert(function.name.clone(), function);
+            }
+            Statement::Expr(expr) => panic!("{} is not a definition", expr),
+        }
+    }
+
+    fn call(functions: &Functions, expression: &str) -> Result<f64, ExprError> {
+        let variables = HashMap::from([("x".to_string(), 2.0)]);
+        Expr::parse(expression)?.eval(&Env::new(&variables).with_functions(functions))
+    }
+
+    #[test]
+    fn test_user_functions() {
+        let mut functions = Functions::new();
+        define(&mut functions, "f(x, y) = x^2 + y");
+        define(&mut functions, "g(y) = f(y, x) * 2");
+        define(&mut functions, "k() = 5");
+        // 参数只在函数体内遮蔽同名变量，其他变量仍然可见
+        assert_eq!(call(&functions, "f(3, 1)"), Ok(10.0));
+        assert_eq!(call(&functions, "g(1) + x"), Ok(8.0));
+        assert_eq!(call(&functions, "k()"), Ok(5.0));
+        assert_eq!(
+            call(&functions, "f(1)"),
+            Err(ExprError::Call("f expects 2 arguments, got 1".to_string()))
+        );
+        assert_eq!(call(&functions, "h(1)"), Err(unknown_function("h")));
+        assert_eq!(functions["f"].to_string(), "f(x, y) = x ^ 2 + y");
+
+        define(&mut functions, "r(n) = r(n - 1)");
+        assert_eq!(
+            call(&functions, "r(3)"),
+            Err(ExprError::Call(format!(
+                "r exceeded the limit of {} nested calls",
+                MAX_CALL_DEPTH
+            )))
+        );
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        for (source, message) in [
+            ("sin(x) = x", "Cannot redefine built-in function sin"),
+            ("f(x, x) = x", "Invalid parameter list for f"),
+            ("f(x,) = x", "Invalid parameter list for f"),
+            ("f(1) = 2", "Invalid parameter list for f"),
+            ("f(a) = a b", "Unexpected token: Variable(\"b\")"),
+            ("f(a) = (a))", "Unexpected token: RightParen"),
+            ("f(a) = a +", "Unexpected end of expression"),
+        ] {
+            assert_eq!(
+                Statement::parse(source),
+                Err(ExprError::Definition(message.to_string()))
+            );
+        }
+        // 函数体里的调用错误仍然是调用错误
+        assert_eq!(
+            Statement::parse("f(a) = sin(a, 1)"),
+            Err(ExprError::Call("sin expects 1 argument, got 2".to_string()))
+        );
+
+        // 不是定义的等式仍然按赋值处理
+        assert!(matches!(
+            Statement::parse("y = f(1)"),
+            Ok(Statement::Expr(_))
+        ));
+    }
+
+    #[test]
+    fn test_precedence_table() {
+        // 从松到紧: ?: || && ==,!= <,<=,>,>= +,- *,/,% 一元 ^
+        for (source, grouped) in [
+            ("1 ? 0 : 1 || 0", "1 ? 0 : (1 || 0)"),
+            ("0 || 1 && 0", "0 || (1 && 0)"),
+            ("1 && 2 == 2", "1 && (2 == 2)"),
+            ("1 == 2 < 3", "1 == (2 < 3)"),
+            ("1 != 2 >= 3", "1 != (2 >= 3)"),
+            ("1 < 2 + 3", "1 < (2 + 3)"),
+            ("1 + 2 % 3", "1 + (2 % 3)"),
+            ("2 * -3", "2 * (-3)"),
+            ("-2 ^ 2", "-(2 ^ 2)"),
+            ("!0 + 1", "(!0) + 1"),
+            ("2 ^ 3 ^ 2", "2 ^ (3 ^ 2)"),
+            ("2 ^ -1", "2 ^ (-1)"),
+            ("7 - 3 - 2", "(7 - 3) - 2"),
+            ("64 / 4 / 2", "(64 / 4) / 2"),
+            ("7 % 4 % 2", "(7 % 4) % 2"),
+            ("3 > 2 > 1", "(3 > 2) > 1"),
+            ("0 ? 1 : 0 ? 2 : 3", "0 ? 1 : (0 ? 2 : 3)"),
+        ] {
+            assert_eq!(Expr::parse(source), Expr::parse(grouped), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_logic_and_comparison() {
+        assert_eq!(eval("7 % 3 + -7 % 3"), Ok(0.0));
+        assert_eq!(eval("x == 2 && x != 3 && x <= 2 && x >= 2"), Ok(1.0));
+        assert_eq!(eval("x < 2 || x > 2"), Ok(0.0));
+        assert_eq!(eval("!x + !0 + !!x"), Ok(2.0));
+        assert_eq!(eval("x > 1 ? 10 : 20"), Ok(10.0));
+        assert_eq!(eval("y = x == 2"), Ok(1.0));
+        // 短路求值，未选中的分支不会出错
+        assert_eq!(eval("0 && 1 / 0"), Ok(0.0));
+        assert_eq!(eval("x || q"), Ok(1.0));
+        assert_eq!(eval("x ? 1 : q"), Ok(1.0));
+        assert_eq!(
+            eval("1 % 0"),
+            Err(ExprError::Invalid("Modulo by zero".to_string()))
+        );
+        assert_eq!(
+            eval("x ? 1"),
+            Err(ExprError::Invalid(
+                "Missing : in conditional expression".to_string()
+            ))
+        );
+        assert!(eval("x & 1").is_err());
+
+        let mut functions = Functions::new();
+        define(&mut functions, "fact(n) = n <= 1 ? 1 : n * fact(n - 1)");
+        assert_eq!(call(&functions, "fact(5)"), Ok(120.0));
+    }
+}
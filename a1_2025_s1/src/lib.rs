@@ -1,8 +1,11 @@
+pub mod block;
 pub mod error;
+pub mod expr;
 
 use anyhow::Result;
-use error::{UQExprError, UQExprResult};
-use std::f64::consts::{E, PI};
+pub use block::Interpreter;
+use error::{ExprError, UQExprError, UQExprResult};
+pub use expr::{Env, Expr, Function, Functions, Statement};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use thiserror::Error;
@@ -30,6 +33,7 @@ pub const DUPLICATE_MSG: &str = "uqexpr: duplicate variables were detected";
 pub const USAGE_MSG: &str = "Usage: ./uqexpr [--init string] [--significantfigures 2..8] [--forloop string] [inputfilename]";
 pub const VARIABLE_MSG: &str = "uqexpr: invalid variable(s) were specified";
 // 是不是可以自己定义错误处理枚举类型
+#[derive(Default)]
 pub struct Config {
     pub init_string_vec: Vec<String>,
     pub init_order: Vec<String>,
@@ -40,6 +44,19 @@ pub struct Config {
     pub filename_flag: bool,
     pub init_map: std::collections::HashMap<String, f64>,
     pub for_loop_struct_vec: Vec<ForLoop>,
+    /// Functions defined in the input, listed by `@print` in definition order.
+    pub functions: Functions,
+    pub function_order: Vec<String>,
+}
+
+impl Config {
+    /// Adds `function`, replacing any earlier definition of the same name.
+    pub fn define_function(&mut self, function: Function) {
+        if !self.functions.contains_key(&function.name) {
+            self.function_order.push(function.name.clone());
+        }
+        self.functions.insert(function.name.clone(), function);
+    }
 }
 pub struct ForLoop {
     pub name: String,
@@ -62,6 +79,8 @@ pub fn handle_command_line_arguments() -> UQExprResult<Config> {
         filename_flag: false,
         init_map: std::collections::HashMap::new(),
         for_loop_struct_vec: Vec::new(),
+        functions: Functions::new(),
+        function_order: Vec::new(),
     };
     let mut i = 1;
 
@@ -94,7 +113,7 @@ pub fn handle_command_line_arguments() -> UQExprResult<Config> {
                         return Err(UQExprError::InvalidExpression("Usage".to_string()));
                     }
                     Ok(x) => {
-                        if x >= 2 && x <= 8 {
+                        if (2..=8).contains(&x) {
                             config.significant_figures = x;
                         } else {
                             return Err(UQExprError::InvalidExpression("Usage".to_string()));
@@ -169,11 +188,7 @@ fn is_valid_var_name(name: &str) -> bool {
     }
 
     // First character must be a letter
-    if !name
-        .chars()
-        .next()
-        .map_or(false, |c| c.is_ascii_alphabetic())
-    {
+    if !name.chars().next().is_some_and(|c| c.is_ascii_alphabetic()) {
         return false;
     }
 
@@ -288,14 +303,8 @@ pub fn check_variable(config: &mut Config) -> Result<(), ExitError> {
         }
 
         // Validate increment direction (must make progress toward end)
-        if start < end {
-            if increment < 0.0 {
-                return Err(ExitError::Variable);
-            }
-        } else if start > end {
-            if increment > 0.0 {
-                return Err(ExitError::Variable);
-            }
+        if (start < end && increment < 0.0) || (start > end && increment > 0.0) {
+            return Err(ExitError::Variable);
         }
 
         // Add to for_loop_struct_vec
@@ -314,285 +323,31 @@ pub fn check_variable(config: &mut Config) -> Result<(), ExitError> {
     Ok(())
 }
 
-// Token types for the expression parser
-#[derive(Debug)]
-enum Token {
-    Number(f64),
-    Plus,
-    Minus,
-    Multiply,
-    Divide,
-    Power,
-    LeftParen,
-    RightParen,
-    Constant(String),
-    Function(String),
-    Variable(String),
-    Equals,
-}
-
-// Tokenizer function to convert expression string into tokens
-fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
-    let mut tokens = Vec::new();
-    let mut chars = expression.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        match c {
-            // Skip whitespace
-            ' ' | '\t' | '\n' | '\r' => continue,
-
-            // Arithmetic operators
-            '+' => tokens.push(Token::Plus),
-            '-' => {
-                // Check if this is a negative sign or subtraction operator
-                if tokens.is_empty()
-                    || matches!(
-                        tokens.last().unwrap(),
-                        Token::LeftParen
-                            | Token::Plus
-                            | Token::Minus
-                            | Token::Multiply
-                            | Token::Divide
-                            | Token::Power
-                            | Token::Equals
-                    )
-                {
-                    // This is a negative sign
-                    tokens.push(Token::Number(-1.0));
-                    tokens.push(Token::Multiply);
-                } else {
-                    // This is a subtraction operator
-                    tokens.push(Token::Minus);
-                }
-            }
-            '*' => tokens.push(Token::Multiply),
-            '/' => tokens.push(Token::Divide),
-            '^' => tokens.push(Token::Power),
-
-            // Parentheses
-            '(' => tokens.push(Token::LeftParen),
-            ')' => tokens.push(Token::RightParen),
-
-            // Equals operator for assignment
-            '=' => tokens.push(Token::Equals),
-
-            // Numbers
-            '0'..='9' | '.' => {
-                let mut num_str = c.to_string();
-                while let Some(&next_c) = chars.peek() {
-                    if next_c.is_ascii_digit() || next_c == '.' {
-                        num_str.push(chars.next().unwrap());
-                    } else {
-                        break;
-                    }
-                }
-                match num_str.parse::<f64>() {
-                    Ok(num) => tokens.push(Token::Number(num)),
-                    Err(_) => return Err(format!("Invalid number: {}", num_str)),
-                }
-            }
-
-            // Constants, functions, and variables (letters)
-            'a'..='z' | 'A'..='Z' => {
-                let mut name = c.to_string();
-                while let Some(&next_c) = chars.peek() {
-                    if next_c.is_ascii_alphabetic() {
-                        name.push(next_c);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-
-                // Check if it's a constant or function, otherwise treat as variable
-                let lowercase_name = name.to_lowercase();
-                match lowercase_name.as_str() {
-                    "pi" | "e" => tokens.push(Token::Constant(lowercase_name)),
-                    "sin" | "exp" => tokens.push(Token::Function(lowercase_name)),
-                    _ => tokens.push(Token::Variable(name)),
-                }
-            }
-
-            // Invalid character
-            _ => return Err(format!("Invalid character: {}", c)),
-        }
-    }
-
-    Ok(tokens)
-}
-
-// Parser struct to hold token iterator and variable map
-struct Parser<'a> {
-    tokens: std::iter::Peekable<std::slice::Iter<'a, Token>>,
-    variables: &'a mut std::collections::HashMap<String, f64>,
-}
-
-impl<'a> Parser<'a> {
-    fn new(tokens: &'a [Token], variables: &'a mut std::collections::HashMap<String, f64>) -> Self {
-        Parser {
-            tokens: tokens.iter().peekable(),
-            variables,
-        }
-    }
-
-    // Parse a primary expression (number, constant, function call, or parenthesized expression)
-    fn parse_primary(&mut self) -> Result<f64, String> {
-        match self.tokens.next() {
-            Some(Token::Number(num)) => Ok(*num),
-
-            Some(Token::Constant(name)) => match name.as_str() {
-                "pi" => Ok(PI),
-                "e" => Ok(E),
-                _ => Err(format!("Unknown constant: {}", name)),
-            },
-
-            Some(Token::Function(name)) => {
-                // Parse function call: function_name(expression)
-                if let Some(Token::LeftParen) = self.tokens.next() {
-                    let arg = self.parse_expression()?;
-                    if let Some(Token::RightParen) = self.tokens.next() {
-                        match name.as_str() {
-                            "sin" => Ok(arg.sin()),
-                            "exp" => Ok(arg.exp()),
-                            _ => Err(format!("Unknown function: {}", name)),
-                        }
-                    } else {
-                        Err("Missing closing parenthesis".to_string())
-                    }
-                } else {
-                    Err("Missing opening parenthesis after function name".to_string())
-                }
-            }
-
-            Some(Token::LeftParen) => {
-                let expr = self.parse_expression()?;
-                if let Some(Token::RightParen) = self.tokens.next() {
-                    Ok(expr)
-                } else {
-                    Err("Missing closing parenthesis".to_string())
-                }
-            }
-
-            Some(Token::Variable(name)) => {
-                // Look up variable in the variables map
-                if let Some(&value) = self.variables.get(name) {
-                    Ok(value)
-                } else {
-                    Err(format!("Unknown variable: {}", name))
-                }
-            }
-
-            Some(token) => Err(format!("Unexpected token: {:?}", token)),
-            None => Err("Unexpected end of expression".to_string()),
-        }
-    }
-
-    // Parse exponentiation (right-associative)
-    fn parse_exponent(&mut self) -> Result<f64, String> {
-        let mut left = self.parse_primary()?;
-
-        while let Some(Token::Power) = self.tokens.peek() {
-            self.tokens.next(); // Consume the ^ token
-            let right = self.parse_primary()?;
-            left = left.powf(right);
-        }
-
-        Ok(left)
-    }
-
-    // Parse multiplication and division
-    fn parse_term(&mut self) -> Result<f64, String> {
-        let mut left = self.parse_exponent()?;
-
-        loop {
-            match self.tokens.peek() {
-                Some(Token::Multiply) => {
-                    self.tokens.next(); // Consume the * token
-                    let right = self.parse_exponent()?;
-                    left *= right;
-                }
-                Some(Token::Divide) => {
-                    self.tokens.next(); // Consume the / token
-                    let right = self.parse_exponent()?;
-                    if right == 0.0 {
-                        return Err("Division by zero".to_string());
-                    }
-                    left /= right;
-                }
-                _ => break,
-            }
-        }
-
-        Ok(left)
-    }
-
-    // Parse assignment expressions: variable = expression
-    fn parse_assignment(&mut self) -> Result<f64, String> {
-        // First check if we have an assignment
-        if let Some(Token::Variable(name)) = self.tokens.peek() {
-            // Look ahead to see if there's an equals sign
-            let mut peek_iter = self.tokens.clone();
-            peek_iter.next(); // Skip the variable
-            if let Some(Token::Equals) = peek_iter.peek() {
-                // It's an assignment: consume the variable and equals sign
-                self.tokens.next(); // Consume the variable
-                self.tokens.next(); // Consume the equals sign
-
-                // Parse the expression on the right-hand side
-                let value = self.parse_expression()?;
-
-                // Update the variable in the map
-                self.variables.insert(name.clone(), value);
-
-                // Return the assigned value
-                return Ok(value);
-            }
-        }
-
-        // Not an assignment, parse as regular expression
-        self.parse_expression()
-    }
-
-    // Parse addition and subtraction
-    fn parse_expression(&mut self) -> Result<f64, String> {
-        let mut left = self.parse_term()?;
-
-        loop {
-            match self.tokens.peek() {
-                Some(Token::Plus) => {
-                    self.tokens.next(); // Consume the + token
-                    let right = self.parse_term()?;
-                    left += right;
-                }
-                Some(Token::Minus) => {
-                    self.tokens.next(); // Consume the - token
-                    let right = self.parse_term()?;
-                    left -= right;
-                }
-                _ => break,
-            }
-        }
-
-        Ok(left)
-    }
-}
-
 // Expression evaluation function with support for advanced features
 pub fn evaluate_expression(
     expression: &str,
     variables: &mut std::collections::HashMap<String, f64>,
-) -> Result<f64, String> {
+) -> Result<f64, ExprError> {
     // Trim whitespace from the expression
     let trimmed = expression.trim();
 
     if trimmed.is_empty() {
-        return Err("Empty expression".to_string());
+        return Err(ExprError::Invalid("Empty expression".to_string()));
     }
 
-    // Tokenize the expression
-    let tokens = tokenize(trimmed)?;
+    evaluate_parsed(&Expr::parse(trimmed)?, variables, &Functions::new())
+}
 
-    // Parse and evaluate the expression
-    let mut parser = Parser::new(&tokens, variables);
-    parser.parse_assignment()
+/// Evaluates an already parsed expression, storing the value if it is an
+/// assignment.
+pub fn evaluate_parsed(
+    expr: &Expr,
+    variables: &mut std::collections::HashMap<String, f64>,
+    functions: &Functions,
+) -> Result<f64, ExprError> {
+    let value = expr.eval(&Env::new(variables).with_functions(functions))?;
+    if let Some(name) = expr.target() {
+        variables.insert(name.to_string(), value);
+    }
+    Ok(value)
 }
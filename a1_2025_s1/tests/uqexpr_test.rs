@@ -23,9 +23,9 @@ fn test_check_input_filename() {
     writeln!(temp_file, "test line 2").unwrap();
     let filename = temp_file.path().to_str().unwrap().to_string();
 
-    // 测试文件读取（注意：当前实现总是返回Err，所以期望Err）
+    // 测试文件读取，返回文件的每一行
     let result = check_input_filename(&filename);
-    assert!(result.is_err());
+    assert_eq!(result.unwrap(), ["test line 1", "test line 2"]);
 
     // 测试不存在的文件
     let result = check_input_filename(&"nonexistent_file.txt".to_string());
@@ -94,3 +94,22 @@ fn test_error_handling() {
     // 测试ExitCodes枚举值
     assert_eq!(ExitCodes::Usage as i32, 18);
 }
+
+// 测试evaluate_expression先解析再求值，赋值会写入变量表
+#[test]
+fn test_evaluate_expression() {
+    let mut variables = std::collections::HashMap::new();
+    variables.insert("x".to_string(), 2.0);
+    assert_eq!(evaluate_expression("x * 3 + 1", &mut variables), Ok(7.0));
+    assert_eq!(evaluate_expression("y = x ^ 3", &mut variables), Ok(8.0));
+    assert_eq!(variables.get("y"), Some(&8.0));
+    assert!(evaluate_expression("z + 1", &mut variables).is_err());
+    assert!(evaluate_expression("  ", &mut variables).is_err());
+
+    // 同一棵语法树可以反复求值
+    let expr = Expr::parse("x = x + 1").unwrap();
+    for _ in 0..3 {
+        evaluate_parsed(&expr, &mut variables, &Functions::new()).unwrap();
+    }
+    assert_eq!(variables.get("x"), Some(&5.0));
+}